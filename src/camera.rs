@@ -1,39 +1,114 @@
 use std::f32;
+use std::ops::Range;
 use std::time::Instant;
 
-use nalgebra::{Point2, Point3, Vector2};
+use nalgebra::{Point2, Point3, Vector2, Vector3};
 use ncollide3d::{math::Isometry, query::Ray};
-use rand::{thread_rng, Rng};
-use rayon::{iter::ParallelIterator, prelude::*};
+use rayon::prelude::*;
 use serde::{Deserialize, Serialize};
 
-use crate::integrators::PathTracingIntegrator;
-use crate::sampling::UniformSampler2;
+use crate::error::{Error, Result};
+use crate::film::{Film, Filter};
+use crate::samplers::Sampler;
+use crate::sampling::ApertureSampler;
 use crate::scene::Scene;
+use crate::settings::RenderSettings;
+
+fn default_focal_length() -> f32 {
+    1.0
+}
+
+fn default_focus_distance() -> f32 {
+    1.0
+}
+
+fn default_sensor_width() -> f32 {
+    36.0
+}
+
+/// Mapping from the screen to the rays leaving the camera, which looks towards its local z axis.
+#[derive(Clone, Copy, Default, Serialize, Deserialize)]
+pub enum Projection {
+    #[default]
+    Perspective,
+    // Parallel rays, over a view of this width in scene units.
+    Orthographic {
+        view_width: f32,
+    },
+    // Panorama of 360 degrees of longitude by 180 degrees of latitude.
+    Equirectangular,
+    // Field of view in degrees across the width of the image, directions beyond it are black.
+    Fisheye {
+        mapping: FisheyeMapping,
+        fov: f32,
+    },
+}
+
+/// Relation between the distance to the image center and the angle to the optical axis.
+#[derive(Clone, Copy, Serialize, Deserialize)]
+pub enum FisheyeMapping {
+    // Distance proportional to the angle.
+    Equidistant,
+    // Distance proportional to the sine of half the angle, preserving solid angles.
+    Equisolid,
+}
+
+// How the extent of the screen is chosen.
+#[derive(Clone, Copy)]
+enum Framing {
+    // Screen dimensions given explicitly, in scene units at the focal length.
+    Screen(Vector2<f32>),
+    // Vertical or horizontal field of view in degrees, the other one follows the resolution.
+    VerticalFov(f32),
+    HorizontalFov(f32),
+    // Focal length in mm for the sensor width of the builder.
+    FocalLengthMm(f32),
+}
 
 pub struct CameraBuilder {
     position: Isometry<f32>,
     focal_length: f32,
-    screen_dimensions: Vector2<f32>,
+    framing: Framing,
+    sensor_width: f32,
     resolution: Vector2<usize>,
+    projection: Projection,
+    aperture_radius: f32,
+    focus_distance: f32,
+    aperture_blades: u32,
+    aperture_rotation: f32,
 }
 
 #[derive(Serialize, Deserialize)]
 pub struct Camera {
     position: Isometry<f32>,
     focal_length: f32,
-    screen_dimensions: Vector2<f32>,
     resolution: Vector2<usize>,
     pixel_dimensions: Vector2<f32>,
+    projection: Projection,
+    // Thin lens model, the camera is a pinhole when the aperture radius is zero. Only used by
+    // the perspective and orthographic projections.
+    aperture_radius: f32,
+    // Distance along the optical axis of the plane in focus.
+    focus_distance: f32,
+    // Polygonal aperture with this number of blades, circular below 3.
+    aperture_blades: u32,
+    // Rotation of the polygonal aperture, in degrees.
+    aperture_rotation: f32,
 }
 
 impl CameraBuilder {
     pub fn new() -> CameraBuilder {
         CameraBuilder {
             position: Isometry::identity(),
-            focal_length: 1.0,
-            screen_dimensions: Vector2::new(1.0, 1.0),
+            focal_length: default_focal_length(),
+            framing: Framing::Screen(Vector2::new(1.0, 1.0)),
+            sensor_width: default_sensor_width(),
             resolution: Vector2::new(100, 100),
+            projection: Projection::default(),
+            aperture_radius: 0.0,
+            focus_distance: default_focus_distance(),
+            aperture_blades: 0,
+            aperture_rotation: 0.0,
         }
     }
 
@@ -48,7 +123,33 @@ impl CameraBuilder {
     }
 
     pub fn screen_dimensions(mut self, new_dimensions: Vector2<f32>) -> Self {
-        self.screen_dimensions = new_dimensions;
+        self.framing = Framing::Screen(new_dimensions);
+        self
+    }
+
+    /// Vertical field of view in degrees, the horizontal one follows the aspect ratio of the
+    /// resolution.
+    pub fn vertical_fov(mut self, new_fov: f32) -> Self {
+        self.framing = Framing::VerticalFov(new_fov);
+        self
+    }
+
+    /// Horizontal field of view in degrees, the vertical one follows the aspect ratio of the
+    /// resolution.
+    pub fn horizontal_fov(mut self, new_fov: f32) -> Self {
+        self.framing = Framing::HorizontalFov(new_fov);
+        self
+    }
+
+    /// Width of the sensor in mm, used with `focal_length_mm`. Defaults to a 36 mm full frame.
+    pub fn sensor_width_mm(mut self, new_sensor_width: f32) -> Self {
+        self.sensor_width = new_sensor_width;
+        self
+    }
+
+    /// Focal length of the lens in mm, which gives the field of view with the sensor width.
+    pub fn focal_length_mm(mut self, new_focal_length: f32) -> Self {
+        self.framing = Framing::FocalLengthMm(new_focal_length);
         self
     }
 
@@ -57,63 +158,368 @@ impl CameraBuilder {
         self
     }
 
+    pub fn projection(mut self, new_projection: Projection) -> Self {
+        self.projection = new_projection;
+        self
+    }
+
+    pub fn aperture_radius(mut self, new_aperture_radius: f32) -> Self {
+        self.aperture_radius = new_aperture_radius;
+        self
+    }
+
+    pub fn focus_distance(mut self, new_focus_distance: f32) -> Self {
+        self.focus_distance = new_focus_distance;
+        self
+    }
+
+    pub fn aperture_blades(mut self, new_blades: u32) -> Self {
+        self.aperture_blades = new_blades;
+        self
+    }
+
+    pub fn aperture_rotation(mut self, new_rotation: f32) -> Self {
+        self.aperture_rotation = new_rotation;
+        self
+    }
+
     pub fn build(self) -> Camera {
+        let aspect_ratio = self.resolution[0] as f32 / self.resolution[1] as f32;
+        let from_width = |width: f32| Vector2::new(width, width / aspect_ratio);
+        let screen_dimensions = match self.framing {
+            Framing::Screen(dimensions) => dimensions,
+            Framing::VerticalFov(fov) => {
+                let height = 2.0 * self.focal_length * (0.5 * fov.to_radians()).tan();
+                Vector2::new(height * aspect_ratio, height)
+            }
+            Framing::HorizontalFov(fov) => {
+                from_width(2.0 * self.focal_length * (0.5 * fov.to_radians()).tan())
+            }
+            Framing::FocalLengthMm(focal_length_mm) => {
+                from_width(self.focal_length * self.sensor_width / focal_length_mm)
+            }
+        };
         let pixel_dimensions = Vector2::new(
-            self.screen_dimensions[0] / self.resolution[0] as f32,
-            self.screen_dimensions[1] / self.resolution[1] as f32,
+            screen_dimensions[0] / self.resolution[0] as f32,
+            screen_dimensions[1] / self.resolution[1] as f32,
         );
         Camera {
             position: self.position,
             focal_length: self.focal_length,
-            screen_dimensions: self.screen_dimensions,
             resolution: self.resolution,
 
-            pixel_dimensions: pixel_dimensions,
+            pixel_dimensions,
+            projection: self.projection,
+            aperture_radius: self.aperture_radius,
+            focus_distance: self.focus_distance,
+            aperture_blades: self.aperture_blades,
+            aperture_rotation: self.aperture_rotation,
+        }
+    }
+}
+
+/// Camera description of a scene file.
+///
+/// The screen is given either by `screen_dimensions`, by one of the fields of view in degrees,
+/// or by a focal length in mm on a sensor of `sensor_width` mm. At most one of
+/// `vertical_fov`, `horizontal_fov`, `focal_length_mm` and `screen_dimensions` can be set.
+#[derive(Serialize, Deserialize)]
+pub struct CameraData {
+    pub position: Isometry<f32>,
+    // Distance of the screen in scene units, only relevant with `screen_dimensions`.
+    #[serde(default = "default_focal_length")]
+    pub focal_length: f32,
+    #[serde(default)]
+    pub screen_dimensions: Option<Vector2<f32>>,
+    #[serde(default)]
+    pub vertical_fov: Option<f32>,
+    #[serde(default)]
+    pub horizontal_fov: Option<f32>,
+    #[serde(default)]
+    pub focal_length_mm: Option<f32>,
+    #[serde(default = "default_sensor_width")]
+    pub sensor_width: f32,
+    pub resolution: Vector2<usize>,
+    #[serde(default)]
+    pub projection: Projection,
+    #[serde(default)]
+    pub aperture_radius: f32,
+    #[serde(default = "default_focus_distance")]
+    pub focus_distance: f32,
+    #[serde(default)]
+    pub aperture_blades: u32,
+    #[serde(default)]
+    pub aperture_rotation: f32,
+}
+
+impl CameraData {
+    pub fn to_camera(self) -> Result<Camera> {
+        match self.projection {
+            Projection::Orthographic { view_width } if view_width.is_nan() || view_width <= 0.0 => {
+                return Err(Error::InvalidCamera {
+                    message: format!("view_width must be positive, not {}", view_width),
+                })
+            }
+            Projection::Fisheye { fov, .. } if !(fov > 0.0 && fov <= 360.0) => {
+                return Err(Error::InvalidCamera {
+                    message: format!(
+                        "fisheye fields of view must be between 0 and 360 degrees, not {}",
+                        fov
+                    ),
+                })
+            }
+            _ => {}
         }
+        let mut builder = CameraBuilder::new()
+            .position(self.position)
+            .focal_length(self.focal_length)
+            .sensor_width_mm(self.sensor_width)
+            .resolution(self.resolution)
+            .projection(self.projection)
+            .aperture_radius(self.aperture_radius)
+            .focus_distance(self.focus_distance)
+            .aperture_blades(self.aperture_blades)
+            .aperture_rotation(self.aperture_rotation);
+        builder = match (
+            self.vertical_fov,
+            self.horizontal_fov,
+            self.focal_length_mm,
+            self.screen_dimensions,
+        ) {
+            (Some(fov), None, None, None) => builder.vertical_fov(check_fov(fov)?),
+            (None, Some(fov), None, None) => builder.horizontal_fov(check_fov(fov)?),
+            (None, None, Some(focal_length), None) if focal_length > 0.0 => {
+                builder.focal_length_mm(focal_length)
+            }
+            (None, None, Some(focal_length), None) => {
+                return Err(Error::InvalidCamera {
+                    message: format!("focal_length_mm must be positive, not {}", focal_length),
+                })
+            }
+            (None, None, None, Some(dimensions)) => builder.screen_dimensions(dimensions),
+            (None, None, None, None) => builder,
+            _ => {
+                return Err(Error::InvalidCamera {
+                    message: "only one of vertical_fov, horizontal_fov, focal_length_mm and \
+                              screen_dimensions can be set"
+                        .to_owned(),
+                })
+            }
+        };
+        Ok(builder.build())
     }
 }
 
+// Fields of view must be strictly between 0 and 180 degrees to span a finite screen.
+fn check_fov(fov: f32) -> Result<f32> {
+    if fov > 0.0 && fov < 180.0 {
+        Ok(fov)
+    } else {
+        Err(Error::InvalidCamera {
+            message: format!(
+                "fields of view must be between 0 and 180 degrees, not {}",
+                fov
+            ),
+        })
+    }
+}
+
+struct Tile {
+    x_range: Range<usize>,
+    y_range: Range<usize>,
+}
+
 impl Camera {
-    pub fn compute_samples(&self, scene: &Scene, n_samples: u32) -> Vec<Vec<Point3<f32>>> {
-        let integrator = PathTracingIntegrator::new();
-        let pixel_sampler = UniformSampler2::new(self.pixel_dimensions);
-        let start_time = Instant::now();
-        let samples = (0..self.resolution[0])
-            //.into_par_iter()
-            .map(|x| {
-                let mut rng = thread_rng();
-                let mut row: Vec<Point3<f32>> = Vec::new();
-                row.resize(self.resolution[1], Point3::new(0.0, 0.0, 0.0));
-                for y in 0..self.resolution[1] {
-                    for s in 0..n_samples {
-                        let x_coord = x as f32 - self.resolution[0] as f32 / 2.0;
-                        let y_coord = -(y as f32 - self.resolution[1] as f32 / 2.0);
-                        let pixel_samples =
-                            Point2::new(rng.gen_range(0.0, 1.0), rng.gen_range(0.0, 1.0));
-                        let pixel_position = pixel_sampler.sample(&pixel_samples);
-                        let ray_target = Point3::new(
-                            x_coord * self.pixel_dimensions[0] + pixel_position[0],
-                            y_coord * self.pixel_dimensions[1] + pixel_position[1],
-                            self.focal_length,
-                        );
-                        let ray_direction = ray_target.coords.normalize();
-                        let initial_ray = Ray::new(Point3::new(0.0, 0.0, 0.0), ray_direction)
-                            .transform_by(&self.position);
-                        let sample_value =
-                            integrator.launch_ray(&initial_ray, scene, &mut rng, true);
-                        row[y] = (row[y] * s as f32 + sample_value) / (s + 1) as f32;
+    pub fn resolution(&self) -> Vector2<usize> {
+        self.resolution
+    }
+
+    fn tiles(&self, tile_size: usize) -> Vec<Tile> {
+        let tile_size = tile_size.max(1);
+        let mut tiles = Vec::new();
+        for x_start in (0..self.resolution[0]).step_by(tile_size) {
+            for y_start in (0..self.resolution[1]).step_by(tile_size) {
+                tiles.push(Tile {
+                    x_range: x_start..(x_start + tile_size).min(self.resolution[0]),
+                    y_range: y_start..(y_start + tile_size).min(self.resolution[1]),
+                });
+            }
+        }
+        tiles
+    }
+
+    // Ray in world space through a point of the screen, centered on the optical axis.
+    fn generate_ray(
+        &self,
+        screen_position: &Point2<f32>,
+        aperture_sampler: &ApertureSampler,
+        sampler: &mut dyn Sampler,
+    ) -> Option<Ray<f32>> {
+        let screen_dimensions = Vector2::new(
+            self.pixel_dimensions[0] * self.resolution[0] as f32,
+            self.pixel_dimensions[1] * self.resolution[1] as f32,
+        );
+        // Position in [-0.5, 0.5] on both axes of the image.
+        let image_position = screen_position.coords.component_div(&screen_dimensions);
+
+        let (origin, direction) = match self.projection {
+            Projection::Perspective => (
+                Point3::origin(),
+                Vector3::new(screen_position[0], screen_position[1], self.focal_length).normalize(),
+            ),
+            Projection::Orthographic { view_width } => {
+                let view_height =
+                    view_width * self.resolution[1] as f32 / self.resolution[0] as f32;
+                (
+                    Point3::new(
+                        image_position[0] * view_width,
+                        image_position[1] * view_height,
+                        0.0,
+                    ),
+                    Vector3::z(),
+                )
+            }
+            Projection::Equirectangular => {
+                let longitude = 2.0 * f32::consts::PI * image_position[0];
+                let latitude = f32::consts::PI * image_position[1];
+                let direction = Vector3::new(
+                    latitude.cos() * longitude.sin(),
+                    latitude.sin(),
+                    latitude.cos() * longitude.cos(),
+                );
+                return Some(Ray::new(Point3::origin(), direction).transform_by(&self.position));
+            }
+            Projection::Fisheye { mapping, fov } => {
+                // Both axes are scaled by the width, so that the circle of the field of view
+                // stays round on images that are not square.
+                let fisheye_position = screen_position.coords / screen_dimensions[0];
+                let radius = 2.0 * fisheye_position.norm();
+                let max_theta = 0.5 * fov.to_radians();
+                let theta = match mapping {
+                    FisheyeMapping::Equidistant => radius * max_theta,
+                    FisheyeMapping::Equisolid => {
+                        let sin_half_theta = radius * (0.5 * max_theta).sin();
+                        if sin_half_theta > 1.0 {
+                            return None;
+                        }
+                        2.0 * sin_half_theta.asin()
                     }
+                };
+                if theta > max_theta || theta > f32::consts::PI {
+                    return None;
+                }
+                let phi = fisheye_position[1].atan2(fisheye_position[0]);
+                let direction = Vector3::new(
+                    theta.sin() * phi.cos(),
+                    theta.sin() * phi.sin(),
+                    theta.cos(),
+                );
+                return Some(Ray::new(Point3::origin(), direction).transform_by(&self.position));
+            }
+        };
+
+        let ray = if self.aperture_radius > 0.0 {
+            // Rays through the lens converge where the pinhole ray crosses the focus plane.
+            let focus_point = origin + direction * (self.focus_distance / direction[2]);
+            let lens_samples = sampler.next_2d();
+            let lens_position = self.aperture_radius * aperture_sampler.sample(&lens_samples);
+            let lens_origin = origin + Vector3::new(lens_position[0], lens_position[1], 0.0);
+            Ray::new(lens_origin, (focus_point - lens_origin).normalize())
+        } else {
+            Ray::new(origin, direction)
+        };
+        Some(ray.transform_by(&self.position))
+    }
+
+    fn render_tile(
+        &self,
+        scene: &Scene,
+        tile: &Tile,
+        settings: &RenderSettings,
+        filter: &dyn Filter,
+        sample_indices: &Range<u32>,
+    ) -> Film {
+        // The samples of the tile also reach the pixels around it, up to the filter radius.
+        let margin = (filter.radius() + 0.5).ceil() as usize;
+        let mut film = Film::with_bounds(
+            tile.x_range.start.saturating_sub(margin)
+                ..(tile.x_range.end + margin).min(self.resolution[0]),
+            tile.y_range.start.saturating_sub(margin)
+                ..(tile.y_range.end + margin).min(self.resolution[1]),
+        );
+        let aperture_sampler =
+            ApertureSampler::new(self.aperture_blades, self.aperture_rotation.to_radians());
+        let mut sampler = settings
+            .sampler
+            .to_sampler(settings.seed, settings.sampler_samples());
+        for x in tile.x_range.clone() {
+            for y in tile.y_range.clone() {
+                let pixel_index = (y * self.resolution[0] + x) as u64;
+                for s in sample_indices.clone() {
+                    sampler.start_sample(pixel_index, s as u64);
+                    let pixel_samples = sampler.next_2d();
+                    let film_position =
+                        Point2::new(x as f32 + pixel_samples[0], y as f32 + pixel_samples[1]);
+                    let screen_position = Point2::new(
+                        (film_position[0] - self.resolution[0] as f32 / 2.0)
+                            * self.pixel_dimensions[0],
+                        -(film_position[1] - self.resolution[1] as f32 / 2.0)
+                            * self.pixel_dimensions[1],
+                    );
+                    let sample_value =
+                        match self.generate_ray(&screen_position, &aperture_sampler, &mut *sampler)
+                        {
+                            Some(ray) => scene.integrator.launch_ray(&ray, scene, &mut *sampler),
+                            None => Vector3::new(0.0, 0.0, 0.0),
+                        };
+                    film.add_sample(&film_position, &sample_value, filter);
                 }
-                row
-            })
+            }
+        }
+        film
+    }
+
+    /// Renders the samples of every pixel with an index in `sample_indices`, and adds them to
+    /// `film`.
+    pub fn render_samples(
+        &self,
+        scene: &Scene,
+        settings: &RenderSettings,
+        filter: &dyn Filter,
+        sample_indices: Range<u32>,
+        film: &mut Film,
+    ) {
+        let tiles = self.tiles(settings.tile_size);
+        let tile_films = tiles
+            .par_iter()
+            .map(|tile| self.render_tile(scene, tile, settings, filter, &sample_indices))
             .collect::<Vec<_>>();
 
+        // Tiles overlap along their borders, merging them in order keeps the sums
+        // deterministic.
+        for tile_film in &tile_films {
+            film.merge(tile_film);
+        }
+    }
+
+    pub fn compute_samples(&self, scene: &Scene, settings: &RenderSettings) -> Result<Film> {
+        let start_time = Instant::now();
+        let filter = settings.filter.to_filter()?;
+        let mut film = Film::new(self.resolution);
+        self.render_samples(
+            scene,
+            settings,
+            &*filter,
+            0..settings.samples_per_pixel,
+            &mut film,
+        );
+
         let end_time = Instant::now() - start_time;
         println!(
             "Total time: {} seconds",
             end_time.as_millis() as f32 / 1000.0
         );
 
-        samples
+        Ok(film)
     }
 }
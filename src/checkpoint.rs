@@ -0,0 +1,177 @@
+use bincode::Options;
+use nalgebra::Vector2;
+use serde::{Deserialize, Serialize};
+
+use std::fs::{self, File};
+use std::io::{self, BufReader, BufWriter};
+use std::path::Path;
+
+use crate::error::{Error, Result};
+use crate::film::Film;
+use crate::scene::SceneData;
+use crate::settings::RenderSettings;
+
+/// State of an interrupted render, from which it can be resumed or extended with more samples.
+///
+/// The samplers only depend on the settings, on the number of samples per pixel they were
+/// created for and on the index of each sample. That number is kept so that the stratified
+/// sampler keeps its strata when more samples are added to the render.
+#[derive(Serialize, Deserialize)]
+pub struct Checkpoint {
+    scene_hash: u64,
+    pub samples_done: u32,
+    // Number of samples per pixel the render was started for.
+    pub samples_per_pixel: u32,
+    // Number of samples per pixel the sampler was created for.
+    pub sampler_samples: u32,
+    pub film: Film,
+}
+
+impl Checkpoint {
+    /// Checkpoint of a render of the scene with hash `scene_hash`, with `settings`.
+    pub fn new(scene_hash: u64, samples_done: u32, settings: &RenderSettings, film: Film) -> Self {
+        Checkpoint {
+            scene_hash,
+            samples_done,
+            samples_per_pixel: settings.samples_per_pixel,
+            sampler_samples: settings.sampler_samples(),
+            film,
+        }
+    }
+
+    /// Reads the checkpoint at `path`, which must have been saved for the same scene, rendered
+    /// at `resolution`.
+    pub fn load(path: &Path, scene_hash: u64, resolution: Vector2<usize>) -> Result<Self> {
+        let file = File::open(path).map_err(|e| Error::from_io(path, e))?;
+        let file_size = file.metadata().map_err(|e| Error::from_io(path, e))?.len();
+        // The lengths stored in the file cannot make it allocate more than its own size.
+        let checkpoint: Checkpoint = bincode::DefaultOptions::new()
+            .with_fixint_encoding()
+            .allow_trailing_bytes()
+            .with_limit(file_size)
+            .deserialize_from(BufReader::new(file))
+            .map_err(|e| bincode_error(path, *e))?;
+        if checkpoint.scene_hash != scene_hash {
+            return Err(Error::SceneChanged {
+                path: path.to_owned(),
+            });
+        }
+        if !checkpoint.film.covers(resolution) {
+            return Err(Error::Parse {
+                path: path.to_owned(),
+                message: "the film does not match the resolution of the camera".to_owned(),
+            });
+        }
+        Ok(checkpoint)
+    }
+
+    /// Writes the checkpoint to a temporary file first, so that an interrupted save keeps the
+    /// previous checkpoint intact.
+    pub fn save(&self, path: &Path) -> Result<()> {
+        let temporary_path = path.with_extension("ckpt.tmp");
+        let file = File::create(&temporary_path).map_err(|e| Error::from_io(&temporary_path, e))?;
+        bincode::serialize_into(BufWriter::new(file), self)
+            .map_err(|e| bincode_error(&temporary_path, *e))?;
+        fs::rename(&temporary_path, path).map_err(|e| Error::from_io(path, e))
+    }
+
+    /// FNV-1a hash of the JSON scene description, without the settings that do not change the
+    /// samples. Files referenced by the scene, such as meshes and textures, are not hashed.
+    pub fn scene_hash(scene_data: &SceneData) -> Result<u64> {
+        let mut value = serde_json::to_value(scene_data).map_err(|e| Error::Serialize {
+            message: e.to_string(),
+        })?;
+        if let Some(settings) = value["settings"].as_object_mut() {
+            for key in &["samples_per_pixel", "threads", "tile_size", "tone_mapping"] {
+                settings.remove(*key);
+            }
+        }
+        Ok(value
+            .to_string()
+            .bytes()
+            .fold(0xcbf2_9ce4_8422_2325, |hash, byte| {
+                (hash ^ u64::from(byte)).wrapping_mul(0x0100_0000_01b3)
+            }))
+    }
+}
+
+fn bincode_error(path: &Path, error: bincode::ErrorKind) -> Error {
+    match error {
+        bincode::ErrorKind::Io(source) if source.kind() != io::ErrorKind::UnexpectedEof => {
+            Error::from_io(path, source)
+        }
+        error => Error::Parse {
+            path: path.to_owned(),
+            message: error.to_string(),
+        },
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::film::FilterData;
+    use nalgebra::{Point2, Vector3};
+    use std::path::PathBuf;
+
+    fn temporary_path(name: &str) -> PathBuf {
+        std::env::temp_dir().join(format!("ray_tracing_{}_{}.ckpt", name, std::process::id()))
+    }
+
+    fn checkpoint(scene_hash: u64) -> Checkpoint {
+        let mut film = Film::new(Vector2::new(3, 2));
+        let filter = FilterData::default().to_filter().unwrap();
+        film.add_sample(
+            &Point2::new(1.5, 0.5),
+            &Vector3::new(1.0, 2.0, 3.0),
+            &*filter,
+        );
+        let settings = RenderSettings {
+            samples_per_pixel: 16,
+            ..RenderSettings::default()
+        };
+        Checkpoint::new(scene_hash, 4, &settings, film)
+    }
+
+    #[test]
+    fn saved_checkpoints_are_loaded() {
+        let path = temporary_path("round_trip");
+        checkpoint(7).save(&path).unwrap();
+        let loaded = Checkpoint::load(&path, 7, Vector2::new(3, 2));
+        fs::remove_file(&path).unwrap();
+
+        let loaded = loaded.unwrap();
+        assert_eq!(loaded.samples_done, 4);
+        assert_eq!(loaded.samples_per_pixel, 16);
+        assert_eq!(loaded.sampler_samples, 16);
+        assert_eq!(loaded.film.sample_count(1, 0), 1);
+        assert_eq!(loaded.film.pixels(), checkpoint(7).film.pixels());
+    }
+
+    #[test]
+    fn checkpoints_of_other_scenes_are_refused() {
+        let path = temporary_path("other_scene");
+        checkpoint(7).save(&path).unwrap();
+        let other_scene = Checkpoint::load(&path, 8, Vector2::new(3, 2));
+        let other_resolution = Checkpoint::load(&path, 7, Vector2::new(2, 3));
+        fs::remove_file(&path).unwrap();
+
+        assert!(matches!(other_scene, Err(Error::SceneChanged { .. })));
+        assert!(matches!(other_resolution, Err(Error::Parse { .. })));
+    }
+
+    #[test]
+    fn scene_hash_ignores_the_sample_count_and_threads() {
+        let scene_data = SceneData::default();
+        let hash = Checkpoint::scene_hash(&scene_data).unwrap();
+
+        let mut more_samples = SceneData::default();
+        more_samples.settings.samples_per_pixel = 1024;
+        more_samples.settings.threads = 3;
+        assert_eq!(Checkpoint::scene_hash(&more_samples).unwrap(), hash);
+
+        let mut other_seed = SceneData::default();
+        other_seed.settings.seed = 1;
+        assert_ne!(Checkpoint::scene_hash(&other_seed).unwrap(), hash);
+    }
+}
@@ -0,0 +1,85 @@
+use std::error;
+use std::fmt;
+use std::io;
+use std::path::{Path, PathBuf};
+
+use rayon::ThreadPoolBuildError;
+
+/// Failure to load a scene or one of the files it references, or to save or resume a render.
+#[derive(Debug)]
+pub enum Error {
+    Io { path: PathBuf, source: io::Error },
+    Parse { path: PathBuf, message: String },
+    MissingField { field: &'static str },
+    UnsupportedShape { message: String },
+    InvalidCamera { message: String },
+    InvalidSettings { message: String },
+    ThreadPool { source: ThreadPoolBuildError },
+    // Error raised while loading an object, with its index in the scene description.
+    Object { index: usize, source: Box<Error> },
+    // Checkpoint saved from another scene description.
+    SceneChanged { path: PathBuf },
+    // Scene description that cannot be converted to JSON to be hashed.
+    Serialize { message: String },
+}
+
+impl Error {
+    /// Error of a file operation, malformed contents are reported as parse errors.
+    pub fn from_io(path: &Path, source: io::Error) -> Self {
+        match source.kind() {
+            io::ErrorKind::InvalidData | io::ErrorKind::InvalidInput => Error::Parse {
+                path: path.to_owned(),
+                message: source.to_string(),
+            },
+            _ => Error::Io {
+                path: path.to_owned(),
+                source,
+            },
+        }
+    }
+
+    pub fn in_object(self, index: usize) -> Self {
+        Error::Object {
+            index,
+            source: Box::new(self),
+        }
+    }
+}
+
+impl fmt::Display for Error {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Error::Io { path, source } => write!(f, "cannot read {}: {}", path.display(), source),
+            Error::Parse { path, message } => {
+                write!(f, "cannot parse {}: {}", path.display(), message)
+            }
+            Error::MissingField { field } => write!(f, "missing field `{}`", field),
+            Error::UnsupportedShape { message } => write!(f, "unsupported shape: {}", message),
+            Error::InvalidCamera { message } => write!(f, "invalid camera: {}", message),
+            Error::InvalidSettings { message } => write!(f, "invalid settings: {}", message),
+            Error::ThreadPool { source } => {
+                write!(f, "cannot create the rendering threads: {}", source)
+            }
+            Error::Object { index, source } => write!(f, "object {}: {}", index, source),
+            Error::SceneChanged { path } => write!(
+                f,
+                "cannot resume {}: the scene changed since the checkpoint",
+                path.display()
+            ),
+            Error::Serialize { message } => write!(f, "cannot serialize the scene: {}", message),
+        }
+    }
+}
+
+impl error::Error for Error {
+    fn source(&self) -> Option<&(dyn error::Error + 'static)> {
+        match self {
+            Error::Io { source, .. } => Some(source),
+            Error::Object { source, .. } => Some(source.as_ref()),
+            Error::ThreadPool { source } => Some(source),
+            _ => None,
+        }
+    }
+}
+
+pub type Result<T> = std::result::Result<T, Error>;
@@ -0,0 +1,245 @@
+use nalgebra::Vector2;
+use serde::{Deserialize, Serialize};
+
+use std::f32::consts::PI;
+
+use crate::error::{Error, Result};
+
+/// Reconstruction filter, weighting the samples by their offset from the pixel center.
+///
+/// Offsets are in pixels, and the weight is zero when one coordinate exceeds the radius.
+pub trait Filter: Send + Sync {
+    fn radius(&self) -> f32;
+    fn evaluate(&self, offset: &Vector2<f32>) -> f32;
+}
+
+fn default_box_radius() -> f32 {
+    0.5
+}
+
+fn default_tent_radius() -> f32 {
+    1.0
+}
+
+fn default_gaussian_radius() -> f32 {
+    1.5
+}
+
+fn default_gaussian_sigma() -> f32 {
+    0.5
+}
+
+fn default_mitchell_radius() -> f32 {
+    2.0
+}
+
+fn default_mitchell_parameter() -> f32 {
+    1.0 / 3.0
+}
+
+fn default_lanczos_radius() -> f32 {
+    3.0
+}
+
+#[derive(Clone, Copy, Serialize, Deserialize)]
+pub enum FilterData {
+    // Unweighted mean of the samples, the pixel only receives its own samples with the
+    // default radius.
+    Box {
+        #[serde(default = "default_box_radius")]
+        radius: f32,
+    },
+    Tent {
+        #[serde(default = "default_tent_radius")]
+        radius: f32,
+    },
+    Gaussian {
+        #[serde(default = "default_gaussian_radius")]
+        radius: f32,
+        // Standard deviation, in pixels.
+        #[serde(default = "default_gaussian_sigma")]
+        sigma: f32,
+    },
+    // Cubic filter with negative lobes, tuned by the blur `b` and the ringing `c`.
+    Mitchell {
+        #[serde(default = "default_mitchell_radius")]
+        radius: f32,
+        #[serde(default = "default_mitchell_parameter")]
+        b: f32,
+        #[serde(default = "default_mitchell_parameter")]
+        c: f32,
+    },
+    // Sinc windowed by a wider sinc, with as many lobes as the radius.
+    Lanczos {
+        #[serde(default = "default_lanczos_radius")]
+        radius: f32,
+    },
+}
+
+impl Default for FilterData {
+    fn default() -> Self {
+        FilterData::Box {
+            radius: default_box_radius(),
+        }
+    }
+}
+
+impl FilterData {
+    /// Filter described by the settings, whose radius and standard deviation must be positive.
+    pub fn to_filter(self) -> Result<Box<dyn Filter>> {
+        let radius = match self {
+            FilterData::Box { radius }
+            | FilterData::Tent { radius }
+            | FilterData::Gaussian { radius, .. }
+            | FilterData::Mitchell { radius, .. }
+            | FilterData::Lanczos { radius } => radius,
+        };
+        if !(radius > 0.0 && radius.is_finite()) {
+            return Err(Error::InvalidSettings {
+                message: format!("the filter radius must be positive, not {}", radius),
+            });
+        }
+        Ok(match self {
+            FilterData::Box { radius } => Box::new(BoxFilter { radius }),
+            FilterData::Tent { radius } => Box::new(TentFilter { radius }),
+            FilterData::Gaussian { sigma, .. } if sigma.is_nan() || sigma <= 0.0 => {
+                return Err(Error::InvalidSettings {
+                    message: format!("the filter sigma must be positive, not {}", sigma),
+                })
+            }
+            FilterData::Gaussian { radius, sigma } => Box::new(GaussianFilter::new(radius, sigma)),
+            FilterData::Mitchell { radius, b, c } => Box::new(MitchellFilter { radius, b, c }),
+            FilterData::Lanczos { radius } => Box::new(LanczosFilter { radius }),
+        })
+    }
+}
+
+// The filters are separable, the weight of an offset is the product of the weights of its
+// coordinates.
+fn separable(offset: &Vector2<f32>, radius: f32, filter: impl Fn(f32) -> f32) -> f32 {
+    if offset[0].abs() > radius || offset[1].abs() > radius {
+        return 0.0;
+    }
+    filter(offset[0].abs()) * filter(offset[1].abs())
+}
+
+pub struct BoxFilter {
+    radius: f32,
+}
+
+impl Filter for BoxFilter {
+    fn radius(&self) -> f32 {
+        self.radius
+    }
+
+    fn evaluate(&self, offset: &Vector2<f32>) -> f32 {
+        separable(offset, self.radius, |_| 1.0)
+    }
+}
+
+pub struct TentFilter {
+    radius: f32,
+}
+
+impl Filter for TentFilter {
+    fn radius(&self) -> f32 {
+        self.radius
+    }
+
+    fn evaluate(&self, offset: &Vector2<f32>) -> f32 {
+        separable(offset, self.radius, |x| self.radius - x)
+    }
+}
+
+pub struct GaussianFilter {
+    radius: f32,
+    sigma: f32,
+    // Value of the gaussian at the radius, subtracted so that the filter reaches zero there.
+    edge: f32,
+}
+
+impl GaussianFilter {
+    pub fn new(radius: f32, sigma: f32) -> Self {
+        let mut filter = GaussianFilter {
+            radius,
+            sigma,
+            edge: 0.0,
+        };
+        filter.edge = filter.gaussian(radius);
+        filter
+    }
+
+    fn gaussian(&self, x: f32) -> f32 {
+        (-x * x / (2.0 * self.sigma * self.sigma)).exp()
+    }
+}
+
+impl Filter for GaussianFilter {
+    fn radius(&self) -> f32 {
+        self.radius
+    }
+
+    fn evaluate(&self, offset: &Vector2<f32>) -> f32 {
+        separable(offset, self.radius, |x| {
+            (self.gaussian(x) - self.edge).max(0.0)
+        })
+    }
+}
+
+pub struct MitchellFilter {
+    radius: f32,
+    b: f32,
+    c: f32,
+}
+
+impl MitchellFilter {
+    // Mitchell-Netravali cubic, defined over [0, 2].
+    fn mitchell(&self, x: f32) -> f32 {
+        let (b, c) = (self.b, self.c);
+        let value = if x < 1.0 {
+            (12.0 - 9.0 * b - 6.0 * c) * x * x * x
+                + (-18.0 + 12.0 * b + 6.0 * c) * x * x
+                + (6.0 - 2.0 * b)
+        } else {
+            (-b - 6.0 * c) * x * x * x
+                + (6.0 * b + 30.0 * c) * x * x
+                + (-12.0 * b - 48.0 * c) * x
+                + (8.0 * b + 24.0 * c)
+        };
+        value / 6.0
+    }
+}
+
+impl Filter for MitchellFilter {
+    fn radius(&self) -> f32 {
+        self.radius
+    }
+
+    fn evaluate(&self, offset: &Vector2<f32>) -> f32 {
+        separable(offset, self.radius, |x| {
+            self.mitchell(2.0 * x / self.radius)
+        })
+    }
+}
+
+pub struct LanczosFilter {
+    radius: f32,
+}
+
+fn sinc(x: f32) -> f32 {
+    if x < 1e-5 {
+        1.0
+    } else {
+        (PI * x).sin() / (PI * x)
+    }
+}
+
+impl Filter for LanczosFilter {
+    fn radius(&self) -> f32 {
+        self.radius
+    }
+
+    fn evaluate(&self, offset: &Vector2<f32>) -> f32 {
+        separable(offset, self.radius, |x| sinc(x) * sinc(x / self.radius))
+    }
+}
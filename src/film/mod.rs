@@ -0,0 +1,190 @@
+use nalgebra::{Point2, Point3, Vector2, Vector3};
+use serde::{Deserialize, Serialize};
+
+use std::ops::Range;
+
+mod filters;
+
+pub use filters::{
+    BoxFilter, Filter, FilterData, GaussianFilter, LanczosFilter, MitchellFilter, TentFilter,
+};
+
+/// Weighted sum of the samples received by each pixel of an image, or of a rectangle of it.
+///
+/// Positions are in pixels from the top left corner of the image, so the center of pixel
+/// `(x, y)` is at `(x + 0.5, y + 0.5)`. A sample contributes to all the pixels whose center
+/// is within the radius of the filter.
+#[derive(Clone, Serialize, Deserialize)]
+pub struct Film {
+    x_range: Range<usize>,
+    y_range: Range<usize>,
+    // Sums of the weighted samples and of their weights, row by row.
+    values: Vec<Vector3<f32>>,
+    weights: Vec<f32>,
+    // Number of samples taken inside each pixel.
+    sample_counts: Vec<u32>,
+}
+
+impl Film {
+    pub fn new(resolution: Vector2<usize>) -> Self {
+        Film::with_bounds(0..resolution[0], 0..resolution[1])
+    }
+
+    pub fn with_bounds(x_range: Range<usize>, y_range: Range<usize>) -> Self {
+        let n_pixels = x_range.len() * y_range.len();
+        Film {
+            x_range,
+            y_range,
+            values: vec![Vector3::zeros(); n_pixels],
+            weights: vec![0.0; n_pixels],
+            sample_counts: vec![0; n_pixels],
+        }
+    }
+
+    /// Whether the film covers exactly the pixels of an image of size `resolution`.
+    pub fn covers(&self, resolution: Vector2<usize>) -> bool {
+        let n_pixels = resolution[0] * resolution[1];
+        self.x_range == (0..resolution[0])
+            && self.y_range == (0..resolution[1])
+            && self.values.len() == n_pixels
+            && self.weights.len() == n_pixels
+            && self.sample_counts.len() == n_pixels
+    }
+
+    fn contains(&self, x: usize, y: usize) -> bool {
+        self.x_range.contains(&x) && self.y_range.contains(&y)
+    }
+
+    fn index(&self, x: usize, y: usize) -> usize {
+        (y - self.y_range.start) * self.x_range.len() + x - self.x_range.start
+    }
+
+    // Pixels of `range` whose center is within `radius` of `position`.
+    fn pixel_range(range: &Range<usize>, position: f32, radius: f32) -> Range<usize> {
+        let start = (position - radius - 0.5).ceil().max(range.start as f32) as usize;
+        let end = ((position + radius - 0.5).floor() + 1.0).min(range.end as f32);
+        start..(end.max(start as f32) as usize)
+    }
+
+    pub fn sample_count(&self, x: usize, y: usize) -> u32 {
+        self.sample_counts[self.index(x, y)]
+    }
+
+    pub fn add_sample(
+        &mut self,
+        position: &Point2<f32>,
+        value: &Vector3<f32>,
+        filter: &dyn Filter,
+    ) {
+        let (x, y) = (position[0].floor(), position[1].floor());
+        if x >= 0.0 && y >= 0.0 && self.contains(x as usize, y as usize) {
+            let index = self.index(x as usize, y as usize);
+            self.sample_counts[index] += 1;
+        }
+
+        let radius = filter.radius();
+        for y in Film::pixel_range(&self.y_range, position[1], radius) {
+            for x in Film::pixel_range(&self.x_range, position[0], radius) {
+                let center = Point2::new(x as f32 + 0.5, y as f32 + 0.5);
+                let weight = filter.evaluate(&(center - position));
+                if weight != 0.0 {
+                    let index = self.index(x, y);
+                    self.values[index] += value * weight;
+                    self.weights[index] += weight;
+                }
+            }
+        }
+    }
+
+    /// Adds the samples of another film, whose pixels must all be covered by this one.
+    pub fn merge(&mut self, other: &Film) {
+        for y in other.y_range.clone() {
+            for x in other.x_range.clone() {
+                let (index, other_index) = (self.index(x, y), other.index(x, y));
+                self.values[index] += other.values[other_index];
+                self.weights[index] += other.weights[other_index];
+                self.sample_counts[index] += other.sample_counts[other_index];
+            }
+        }
+    }
+
+    /// Filtered value of each pixel, indexed by column and then row.
+    pub fn pixels(&self) -> Vec<Vec<Point3<f32>>> {
+        self.x_range
+            .clone()
+            .map(|x| {
+                self.y_range
+                    .clone()
+                    .map(|y| {
+                        let index = self.index(x, y);
+                        let weight = self.weights[index];
+                        if weight != 0.0 {
+                            Point3::from(self.values[index] / weight)
+                        } else {
+                            Point3::origin()
+                        }
+                    })
+                    .collect()
+            })
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn filters() -> Vec<FilterData> {
+        vec![
+            FilterData::Box { radius: 0.5 },
+            FilterData::Box { radius: 1.5 },
+            FilterData::Tent { radius: 1.0 },
+            FilterData::Gaussian {
+                radius: 1.5,
+                sigma: 0.5,
+            },
+            FilterData::Mitchell {
+                radius: 2.0,
+                b: 1.0 / 3.0,
+                c: 1.0 / 3.0,
+            },
+            FilterData::Lanczos { radius: 3.0 },
+        ]
+    }
+
+    #[test]
+    fn single_sample_keeps_its_value() {
+        let position = Point2::new(3.3, 2.6);
+        let value = Vector3::new(0.25, 1.0, 4.0);
+        for filter_data in filters() {
+            let filter = filter_data.to_filter().unwrap();
+            // The sample reaches both tiles, which are merged as when rendering.
+            let mut film = Film::new(Vector2::new(6, 5));
+            for x_range in &[0..3, 3..6] {
+                let mut tile = Film::with_bounds(x_range.clone(), 0..5);
+                tile.add_sample(&position, &value, &*filter);
+                film.merge(&tile);
+            }
+            let pixels = film.pixels();
+            assert_eq!(film.sample_count(3, 2), 1);
+            assert!((pixels[3][2].coords - value).norm() < 1.0e-5);
+        }
+    }
+
+    #[test]
+    fn invalid_filters_are_rejected() {
+        for filter_data in &[
+            FilterData::Box { radius: 0.0 },
+            FilterData::Tent { radius: -1.0 },
+            FilterData::Lanczos {
+                radius: f32::INFINITY,
+            },
+            FilterData::Gaussian {
+                radius: 1.5,
+                sigma: 0.0,
+            },
+        ] {
+            assert!(filter_data.to_filter().is_err());
+        }
+    }
+}
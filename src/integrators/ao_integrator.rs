@@ -1,13 +1,14 @@
-use nalgebra::{Point2, Point3, Vector3};
+use nalgebra::Vector3;
 use ncollide3d::{
     pipeline::object::CollisionGroups,
     query::{Ray, RayIntersection},
     shape::FeatureId,
 };
-use rand::Rng;
 use std::f32;
 use std::f32::consts::PI;
 
+use crate::integrators::Integrator;
+use crate::samplers::Sampler;
 use crate::sampling::UniformHemisphereSampler;
 use crate::scene::Scene;
 
@@ -19,8 +20,10 @@ impl AOIntegrator {
     pub fn new(range: f32) -> Self {
         AOIntegrator { range }
     }
+}
 
-    pub fn launch_ray<R: Rng>(&self, ray: &Ray<f32>, scene: &Scene, rng: &mut R) -> Point3<f32> {
+impl Integrator for AOIntegrator {
+    fn launch_ray(&self, ray: &Ray<f32>, scene: &Scene, sampler: &mut dyn Sampler) -> Vector3<f32> {
         let mut min_toi = f32::MAX;
         let mut min_intersection =
             RayIntersection::new(0.0, Vector3::new(0.0, 0.0, 0.0), FeatureId::Unknown);
@@ -37,15 +40,15 @@ impl AOIntegrator {
             }
         }
         if !found_intersection {
-            return Point3::new(0.0, 0.0, 0.0);
+            return Vector3::new(0.0, 0.0, 0.0);
         }
 
-        //let sampler = CosineWeightedHemisphereSampler;
-        let sampler = UniformHemisphereSampler;
-        let ray_samples = Point2::new(rng.gen_range(0.0, 1.0), rng.gen_range(0.0, 1.0));
+        //let hemisphere_sampler = CosineWeightedHemisphereSampler;
+        let ray_samples = sampler.next_2d();
+        let hemisphere_sampler = UniformHemisphereSampler;
         let new_ray_origin = ray.point_at(min_intersection.toi - 0.001);
         let (new_ray_direction, new_ray_probability) =
-            sampler.sample(&ray_samples, &min_intersection.normal);
+            hemisphere_sampler.sample(&ray_samples, &min_intersection.normal);
         let new_ray = Ray::new(new_ray_origin, new_ray_direction);
 
         for _ in scene.collision_world.interferences_with_ray(
@@ -53,8 +56,8 @@ impl AOIntegrator {
             self.range,
             &CollisionGroups::new(),
         ) {
-            return Point3::new(0.0, 0.0, 0.0);
+            return Vector3::new(0.0, 0.0, 0.0);
         }
-        (1.0 / (2.0 * PI)) * Point3::new(1.0, 1.0, 1.0) / new_ray_probability
+        (1.0 / (2.0 * PI)) * Vector3::new(1.0, 1.0, 1.0) / new_ray_probability
     }
 }
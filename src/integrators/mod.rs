@@ -1,3 +1,14 @@
+use nalgebra::Vector3;
+use ncollide3d::query::Ray;
+use serde::{Deserialize, Serialize};
+
+use crate::samplers::Sampler;
+use crate::scene::Scene;
+
+pub trait Integrator: Send + Sync {
+    fn launch_ray(&self, ray: &Ray<f32>, scene: &Scene, sampler: &mut dyn Sampler) -> Vector3<f32>;
+}
+
 mod ao_integrator;
 mod normal_integrator;
 mod path_tracing;
@@ -5,3 +16,68 @@ mod path_tracing;
 pub use ao_integrator::AOIntegrator;
 pub use normal_integrator::NormalIntegrator;
 pub use path_tracing::PathTracingIntegrator;
+
+fn default_roulette_threshold() -> f32 {
+    0.9
+}
+
+fn default_min_depth() -> u32 {
+    3
+}
+
+fn default_max_depth() -> u32 {
+    64
+}
+
+fn default_ao_range() -> f32 {
+    1.0
+}
+
+#[derive(Serialize, Deserialize)]
+pub enum IntegratorData {
+    PathTracing {
+        // Upper bound of the Russian roulette survival probability, paths survive with the
+        // probability of their largest throughput component below it.
+        #[serde(default = "default_roulette_threshold")]
+        roulette_threshold: f32,
+        // Number of bounces before Russian roulette starts.
+        #[serde(default = "default_min_depth")]
+        min_depth: u32,
+        // Maximum number of bounces of a path.
+        #[serde(default = "default_max_depth")]
+        max_depth: u32,
+    },
+    AmbientOcclusion {
+        #[serde(default = "default_ao_range")]
+        range: f32,
+    },
+    Normal,
+}
+
+impl Default for IntegratorData {
+    fn default() -> Self {
+        IntegratorData::PathTracing {
+            roulette_threshold: default_roulette_threshold(),
+            min_depth: default_min_depth(),
+            max_depth: default_max_depth(),
+        }
+    }
+}
+
+impl IntegratorData {
+    pub fn to_integrator(self) -> Box<dyn Integrator> {
+        match self {
+            IntegratorData::PathTracing {
+                roulette_threshold,
+                min_depth,
+                max_depth,
+            } => Box::new(PathTracingIntegrator::new(
+                roulette_threshold,
+                min_depth,
+                max_depth,
+            )),
+            IntegratorData::AmbientOcclusion { range } => Box::new(AOIntegrator::new(range)),
+            IntegratorData::Normal => Box::new(NormalIntegrator),
+        }
+    }
+}
@@ -1,166 +1,200 @@
-use nalgebra::{Point2, Point3, Vector3};
-use ncollide3d::{
-    pipeline::object::CollisionGroups,
-    query::{Ray, RayIntersection},
-    shape::FeatureId,
-};
-use rand::Rng;
+use nalgebra::{Point3, Vector3};
+use ncollide3d::query::Ray;
 use std::f32;
 
+use crate::integrators::Integrator;
 use crate::math::vector_traits::{ToGlobal, ToLocal};
-use crate::object::WorldObjectData;
-use crate::sampling::UniformShapeSampler;
+use crate::object::HitRecord;
+use crate::samplers::Sampler;
 use crate::scene::Scene;
 
 pub struct PathTracingIntegrator {
-    roulette_threshold: f32,
+    // Upper bound of the Russian roulette survival probability, so that even bright paths
+    // are sometimes terminated.
+    max_survival_probability: f32,
+    min_depth: u32,
+    max_depth: u32,
 }
 
 impl PathTracingIntegrator {
-    pub fn new() -> Self {
+    pub fn new(max_survival_probability: f32, min_depth: u32, max_depth: u32) -> Self {
         PathTracingIntegrator {
-            roulette_threshold: 0.9f32,
+            max_survival_probability,
+            min_depth,
+            max_depth,
         }
     }
 
-    pub fn launch_ray<R: Rng>(
-        &self,
-        ray: &Ray<f32>,
-        scene: &Scene,
-        rng: &mut R,
-        count_emission: bool,
-    ) -> Vector3<f32> {
+    fn trace(&self, ray: &Ray<f32>, scene: &Scene, sampler: &mut dyn Sampler) -> Vector3<f32> {
         let mut sample_value = Vector3::new(0.0, 0.0, 0.0);
-
-        let mut min_toi = f32::MAX;
-        let mut min_intersection =
-            RayIntersection::new(0.0, Vector3::new(0.0, 0.0, 0.0), FeatureId::Unknown);
-        let mut min_data = &WorldObjectData::default();
-
-        match scene.collision_world.first_interference_with_ray(
-            &ray,
-            f32::MAX,
-            &CollisionGroups::new(),
-        ) {
-            Some(intersection) => {
-                min_toi = intersection.inter.toi;
-                min_intersection = intersection.inter;
-                min_data = intersection.co.data();
-            }
-            None => {
-                return Vector3::new(0.0, 0.0, 0.0);
+        let mut throughput = Vector3::new(1.0, 1.0, 1.0);
+        let mut ray = *ray;
+        // Solid angle density with which the previous bounce sampled `ray`, along with the
+        // point of that bounce, or `None` when emission must be fully accounted for (camera
+        // rays and specular bounces, that light sampling cannot reach).
+        let mut bsdf_probability: Option<(f32, Point3<f32>)> = None;
+
+        for depth in 0..=self.max_depth {
+            let intersection = match scene.intersect(&ray, f32::MAX) {
+                Some(intersection) => intersection,
+                None => break,
+            };
+            let hit = HitRecord::new(&ray, intersection.co, &intersection.inter);
+            let data = intersection.co.data();
+
+            // Emissive material contribution
+            if let Some((intensity, color)) = data.emission_at(&hit) {
+                let weight = match (bsdf_probability, scene.emitter(intersection.handle, &hit)) {
+                    (Some((bsdf_probability, previous_point)), Some(emitter)) => {
+                        let emitter_object = scene
+                            .collision_world
+                            .collision_object(emitter.handle)
+                            .unwrap();
+                        let light_probability = emitter.sampler.pdf_from(
+                            emitter_object.position(),
+                            &previous_point,
+                            &hit.point,
+                            &hit.normal,
+                        ) / scene.emitters.len() as f32;
+                        power_heuristic(bsdf_probability, light_probability)
+                    }
+                    _ => 1.0,
+                };
+                sample_value += weight * *intensity * throughput.component_mul(color);
             }
-        }
 
-        let emission = &min_data.emission;
-        let bsdf = &min_data.bsdf;
-        let normal = &min_intersection.normal;
-
-        // Emissive material contribution
-        if count_emission {
-            match emission {
-                Some((intensity, color)) => {
-                    //return sample_value;
-                    sample_value += *intensity * *color;
-                }
-                None => {}
+            let bsdf_function = match data.bsdf_at(&hit) {
+                Some(bsdf_function) => bsdf_function,
+                None => break,
+            };
+            if depth == self.max_depth {
+                break;
             }
-        }
-
-        match bsdf {
-            Some(bsdf_function) => {
-                let local_incident_vector = ray.dir.to_local(&normal);
-
-                // Light sampling
-                let emitter_index = rng.gen_range(0, scene.emitters.len());
-                let emitter_handle = scene.emitters[emitter_index];
 
+            // The same dimensions are drawn at each bounce, whether they are used or not, to
+            // keep the sample sequences aligned between the paths of a pixel.
+            let light_choice = sampler.next_1d();
+            let emitter_samples = sampler.next_2d();
+            let bsdf_samples = sampler.next_2d();
+            let roulette_sample = sampler.next_1d();
+
+            // BSDFs are expressed around the shading normal, which points outside of the
+            // object, the ray may come from either side.
+            let local_incident_vector = ray.dir.to_local(&hit.shading_normal);
+
+            // Light sampling, pointless for Dirac BSDFs
+            if !scene.emitters.is_empty() && bsdf_function.is_diffuse() {
+                let emitter_index = ((light_choice * scene.emitters.len() as f32) as usize)
+                    .min(scene.emitters.len() - 1);
+                let emitter = &scene.emitters[emitter_index];
                 let emitter_object = scene
                     .collision_world
-                    .collision_object(emitter_handle)
+                    .collision_object(emitter.handle)
                     .unwrap();
-                let emitter_shape = emitter_object.shape();
-                let emitter_data = emitter_object.data();
-                let emitter_position = emitter_object.position();
-                let emission_values = emitter_data.emission.unwrap();
+                let emission_values = emitter.emission;
 
                 // Sample point on emitter
-                let shape_sampler = UniformShapeSampler;
-                let emitter_samples = Point2::new(rng.gen_range(0.0, 1.0), rng.gen_range(0.0, 1.0));
-                let (sampled_point, probability) =
-                    shape_sampler.sample(&emitter_shape, &emitter_position, &emitter_samples);
-
-                //if normal[0] != 0.0 {
-                //println!("{:?}, {:?}", ray.dir, normal);
-                //}
-                let current_intersection_point = ray.point_at(min_toi) + 0.001f32 * normal;
-                let mut emitter_dir = (sampled_point - current_intersection_point);
+                let (sampled_point, _, probability) = emitter.sampler.sample_from(
+                    emitter_object.position(),
+                    &hit.point,
+                    &emitter_samples,
+                );
+
+                let shadow_origin =
+                    offset_point(&hit.point, &hit.normal, &(sampled_point - hit.point));
+                let mut emitter_dir = sampled_point - shadow_origin;
                 let emitter_dist = emitter_dir.norm();
                 emitter_dir /= emitter_dist;
-                //println!(
-                //"Light pos :{:?}, Object pos: {:?}, Emitter dir: {:?}",
-                //sampled_point, current_intersection_point, emitter_dir
-                //);
-                //println!("{}", emitter_dist);
-                let emitter_ray = Ray::new(current_intersection_point.clone(), emitter_dir);
-                match scene.collision_world.first_interference_with_ray(
-                    &emitter_ray,
-                    emitter_dist - 0.001f32,
-                    &CollisionGroups::new(),
-                ) {
-                    Some(intersection) => {
-                        let light_toi = intersection.inter.toi;
-                        ////if min_toi < (emitter_dist - 0.001f32) {
-                        //if light_toi < 0.99f32 {
-                        //println!(
-                        //"Obstructed, toi: {}, inter_limit: {}, sampled_point: {:?}, inter_point: {:?}",
-                        //light_toi,
-                        //emitter_dist - 0.01f32,
-                        //sampled_point,
-                        //emitter_ray.point_at(light_toi)
-                        //);
-                        //} else {
-                        ////println!("Light sampling successful: toi: {}", light_toi);
-                        //sample_value += emission_values.0 * emission_values.1 / (probability);
-                        //}
-                    }
-                    None => {
-                        //println!("Light sampling successful");
-                        let bsdf_value = bsdf_function
-                            .eval(&emitter_dir.to_local(&normal), &-local_incident_vector);
-                        sample_value += emission_values.0
-                            * bsdf_value.component_mul(&emission_values.1)
-                            / (emitter_dist * probability);
-                    }
+                let local_emitter_dir = emitter_dir.to_local(&hit.shading_normal);
+
+                let bsdf_value =
+                    bsdf_function.eval(&hit, &local_emitter_dir, &-local_incident_vector);
+                let emitter_ray = Ray::new(shadow_origin, emitter_dir);
+                if probability.is_finite()
+                    && bsdf_value != Vector3::new(0.0, 0.0, 0.0)
+                    && scene
+                        .intersect(&emitter_ray, emitter_dist - 0.001f32)
+                        .is_none()
+                {
+                    // Solid angle density of the direction, including the emitter choice.
+                    let light_probability = probability / scene.emitters.len() as f32;
+                    let weight = power_heuristic(
+                        light_probability,
+                        bsdf_function.pdf(&hit, &local_incident_vector, &local_emitter_dir),
+                    );
+                    sample_value += weight
+                        * emission_values.0
+                        * throughput
+                            .component_mul(&bsdf_value)
+                            .component_mul(&emission_values.1)
+                        * local_emitter_dir[2].abs()
+                        / light_probability;
                 }
+            }
 
-                let roulette_sample = rng.gen_range(0.0, 1.0);
-                if roulette_sample > self.roulette_threshold {
-                    return sample_value;
-                }
+            // BSDF sampling
+            let (local_new_dir, bsdf_value, probability) =
+                bsdf_function.sample(&hit, &local_incident_vector, &bsdf_samples);
+            if probability <= 0.0 {
+                break;
+            }
 
-                // BSDF sampling
-                let bsdf_samples = Point2::new(rng.gen_range(0.0, 1.0), rng.gen_range(0.0, 1.0));
-                let (local_new_dir, bsdf_value, bsdf_probability) =
-                    bsdf_function.sample(&local_incident_vector, &bsdf_samples);
-                let global_new_dir = local_new_dir.to_global(&normal).normalize();
-                let new_ray = Ray::new(current_intersection_point, global_new_dir);
+            if bsdf_function.is_diffuse() {
+                let cos_theta = local_new_dir[2].abs();
+                throughput = throughput.component_mul(&bsdf_value) * cos_theta / probability;
+                bsdf_probability = Some((probability, hit.point));
+            } else {
+                throughput = throughput.component_mul(&bsdf_value) / probability;
+                bsdf_probability = None;
+            }
 
-                let mut new_count_emission = count_emission && !bsdf_function.is_diffuse();
+            let global_new_dir = local_new_dir.to_global(&hit.shading_normal).normalize();
+            ray = Ray::new(
+                offset_point(&hit.point, &hit.normal, &global_new_dir),
+                global_new_dir,
+            );
 
-                let bounce_value = self.launch_ray(&new_ray, scene, rng, new_count_emission);
+            // A degenerate sample can make the throughput infinite or NaN, which the roulette
+            // would never terminate.
+            if !throughput.iter().all(|c| c.is_finite()) {
+                break;
+            }
 
-                if bsdf_function.is_diffuse() {
-                    let cos_theta = local_new_dir[2];
-                    sample_value += bounce_value.component_mul(&bsdf_value) * cos_theta
-                        / (bsdf_probability * self.roulette_threshold);
-                } else {
-                    sample_value += bounce_value.component_mul(&bsdf_value)
-                        / (bsdf_probability * self.roulette_threshold);
+            // Russian roulette, dim paths are more likely to be terminated
+            if depth + 1 >= self.min_depth {
+                let survival_probability = throughput.max().min(self.max_survival_probability);
+                if roulette_sample >= survival_probability {
+                    break;
                 }
+                throughput /= survival_probability;
             }
-            None => {}
         }
         sample_value
     }
 }
+
+impl Integrator for PathTracingIntegrator {
+    fn launch_ray(&self, ray: &Ray<f32>, scene: &Scene, sampler: &mut dyn Sampler) -> Vector3<f32> {
+        self.trace(ray, scene, sampler)
+    }
+}
+
+// Veach's power heuristic with an exponent of 2.
+fn power_heuristic(probability: f32, other_probability: f32) -> f32 {
+    let p2 = probability * probability;
+    let other_p2 = other_probability * other_probability;
+    if p2 + other_p2 > 0.0 {
+        p2 / (p2 + other_p2)
+    } else {
+        0.0
+    }
+}
+
+// Moves `point` off the surface, on the side that `dir` leaves it from.
+fn offset_point(point: &Point3<f32>, normal: &Vector3<f32>, dir: &Vector3<f32>) -> Point3<f32> {
+    if dir.dot(normal) >= 0.0 {
+        point + 0.001f32 * normal
+    } else {
+        point - 0.001f32 * normal
+    }
+}
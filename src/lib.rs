@@ -0,0 +1,41 @@
+//! Physically based ray tracer built on ncollide.
+//!
+//! A scene is usually described by a `SceneData`, deserialized from JSON, and converted into a
+//! renderable `Scene` whose `capture` returns the radiance of each pixel.
+
+mod camera;
+mod checkpoint;
+mod error;
+mod film;
+mod integrators;
+mod math;
+mod object;
+mod output;
+mod samplers;
+mod sampling;
+mod scene;
+mod settings;
+mod shaders;
+mod textures;
+
+pub use camera::{Camera, CameraBuilder, CameraData, FisheyeMapping, Projection};
+pub use checkpoint::Checkpoint;
+pub use error::{Error, Result};
+pub use film::{
+    BoxFilter, Film, Filter, FilterData, GaussianFilter, LanczosFilter, MitchellFilter, TentFilter,
+};
+pub use integrators::{
+    AOIntegrator, Integrator, IntegratorData, NormalIntegrator, PathTracingIntegrator,
+};
+pub use object::shapes::{Falloff, Shape};
+pub use object::{HitRecord, ObjectData};
+pub use output::{
+    save_image, write_exr, write_pfm, write_rgbe, ExrPixelType, ToneMapOperator, ToneMapping,
+};
+pub use samplers::{
+    HaltonSampler, IndependentSampler, Sampler, SamplerData, SobolSampler, StratifiedSampler,
+};
+pub use scene::{Scene, SceneData};
+pub use settings::RenderSettings;
+pub use shaders::{ConductorMaterial, Shader};
+pub use textures::{ColorData, TextureData, WrapMode};
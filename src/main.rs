@@ -1,122 +1,312 @@
-mod camera;
-mod integrators;
-mod math;
-mod object;
-mod sampling;
-mod scene;
-mod shaders;
-
+use std::env;
+use std::error::Error;
 use std::fs::File;
-use std::io::Write;
-
-use image::RgbImage;
-use nalgebra::{Point3, Vector2, Vector3};
-use ncollide3d::math::Isometry;
-
-use crate::camera::CameraBuilder;
-use crate::object::{shapes::Shape, ObjectData};
-use crate::scene::SceneData;
-use crate::shaders::Shader;
-
-fn add_objects_to_scene(scene: &mut SceneData, obj_path: String) {
-    let mirror_ball = ObjectData {
-        shape: Some(Shape::Ball(0.5f32)),
-        position: Some(Isometry::translation(0.0, 0.0, 0.0)),
-        bsdf: Some(Shader::Mirror),
-        ..Default::default()
+use std::io::BufReader;
+use std::path::{Path, PathBuf};
+use std::process;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+
+use nalgebra::Point3;
+use ray_tracing::{save_image, Checkpoint, ExrPixelType, Film, Scene, SceneData, ToneMapOperator};
+
+const USAGE: &str = "Usage: ray_tracing render <scene.json> [options]
+
+Options:
+    -o, --output <path>     output image, .exr, .pfm and .hdr files keep the linear radiance
+    --half                  write OpenEXR images with half precision floats
+    --spp <samples>         number of samples per pixel, or +<samples> to add to a resumed render
+    --threads <count>       number of rendering threads, 0 uses one per core
+    --seed <seed>           seed of the random sequences
+    --tonemap <operator>    tone mapping of 8-bit images: clamp, reinhard, filmic or aces
+    --exposure <stops>      exposure correction of 8-bit images
+    --progressive           render one sample per pixel at a time, Ctrl-C stops the render
+                            and writes the current image
+    --checkpoint-passes <n> progressive render writing a checkpoint every n passes
+    --checkpoint-seconds <s>
+                            progressive render writing a checkpoint every s seconds
+    --resume <checkpoint>   continue the progressive render saved in a checkpoint
+
+Checkpoints write the current linear image next to the output image with a .partial.exr
+extension, whatever the output format. Progressive renders also save their state there, with a
+.ckpt extension.";
+
+enum SampleCount {
+    Total(u32),
+    // Samples added to the ones of the resumed render.
+    Additional(u32),
+}
+
+impl std::str::FromStr for SampleCount {
+    type Err = std::num::ParseIntError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.strip_prefix('+') {
+            Some(additional) => additional.parse().map(SampleCount::Additional),
+            None => s.parse().map(SampleCount::Total),
+        }
+    }
+}
+
+struct RenderOptions {
+    scene_path: String,
+    output_path: String,
+    exr_pixel_type: ExrPixelType,
+    n_samples: Option<SampleCount>,
+    n_threads: Option<usize>,
+    seed: Option<u64>,
+    tone_map_operator: Option<ToneMapOperator>,
+    exposure: Option<f32>,
+    progressive: bool,
+    checkpoint_passes: Option<u32>,
+    checkpoint_interval: Option<Duration>,
+    resume_path: Option<String>,
+}
+
+fn parse_value<T: std::str::FromStr>(flag: &str, value: Option<&String>) -> Result<T, String> {
+    let value = value.ok_or_else(|| format!("missing value for {}", flag))?;
+    value
+        .parse()
+        .map_err(|_| format!("invalid value for {}: {}", flag, value))
+}
+
+fn parse_args(args: &[String]) -> Result<RenderOptions, String> {
+    match args.first().map(|s| s.as_str()) {
+        Some("render") => {}
+        Some(command) => return Err(format!("unknown command: {}", command)),
+        None => return Err("missing command".to_owned()),
+    }
+
+    let mut scene_path = None;
+    let mut options = RenderOptions {
+        scene_path: String::new(),
+        output_path: "output.png".to_owned(),
+        exr_pixel_type: ExrPixelType::Float,
+        n_samples: None,
+        n_threads: None,
+        seed: None,
+        tone_map_operator: None,
+        exposure: None,
+        progressive: false,
+        checkpoint_passes: None,
+        checkpoint_interval: None,
+        resume_path: None,
     };
-    scene.add_object(mirror_ball);
-
-    let left_green_wall = ObjectData {
-        shape: Some(Shape::Cuboid(Vector3::new(10.0, 0.1, 10.0))),
-        position: Some(Isometry::translation(-5.0, 3.0, -5.0)),
-        bsdf: Some(Shader::Lambert(Vector3::new(0.0, 1.0, 0.0))),
-        //bsdf: Some(Shader::Mirror),
-        ..Default::default()
+
+    let mut args = args[1..].iter();
+    while let Some(arg) = args.next() {
+        match arg.as_str() {
+            "-o" | "--output" => options.output_path = parse_value(arg, args.next())?,
+            "--half" => options.exr_pixel_type = ExrPixelType::Half,
+            "--spp" => options.n_samples = Some(parse_value(arg, args.next())?),
+            "--threads" => options.n_threads = Some(parse_value(arg, args.next())?),
+            "--seed" => options.seed = Some(parse_value(arg, args.next())?),
+            "--tonemap" => options.tone_map_operator = Some(parse_value(arg, args.next())?),
+            "--exposure" => options.exposure = Some(parse_value(arg, args.next())?),
+            "--progressive" => options.progressive = true,
+            "--checkpoint-passes" => {
+                options.checkpoint_passes = Some(parse_value(arg, args.next())?);
+                options.progressive = true;
+            }
+            "--checkpoint-seconds" => {
+                let seconds: f32 = parse_value(arg, args.next())?;
+                // Durations cannot hold infinite values, or ones much larger than this bound.
+                if !(seconds > 0.0 && seconds <= u32::MAX as f32) {
+                    return Err("--checkpoint-seconds must be a positive duration".to_owned());
+                }
+                options.checkpoint_interval = Some(Duration::from_secs_f32(seconds));
+                options.progressive = true;
+            }
+            "--resume" => {
+                options.resume_path = Some(parse_value(arg, args.next())?);
+                options.progressive = true;
+            }
+            flag if flag.starts_with('-') => return Err(format!("unknown option: {}", flag)),
+            path => {
+                if scene_path.is_some() {
+                    return Err(format!("unexpected argument: {}", path));
+                }
+                scene_path = Some(path.to_owned());
+            }
+        }
+    }
+
+    options.scene_path = scene_path.ok_or_else(|| "missing scene file".to_owned())?;
+    if let Some(SampleCount::Total(0)) = options.n_samples {
+        return Err("--spp must be at least 1".to_owned());
+    }
+    if options.checkpoint_passes == Some(0) {
+        return Err("--checkpoint-passes must be at least 1".to_owned());
+    }
+    Ok(options)
+}
+
+fn load_scene_data(path: &str) -> Result<SceneData, ray_tracing::Error> {
+    let path = Path::new(path);
+    let file = File::open(path).map_err(|e| ray_tracing::Error::from_io(path, e))?;
+    serde_json::from_reader(BufReader::new(file)).map_err(|e| ray_tracing::Error::Parse {
+        path: path.to_owned(),
+        message: e.to_string(),
+    })
+}
+
+fn render(options: &RenderOptions) -> Result<(), Box<dyn Error>> {
+    let mut scene_data = load_scene_data(&options.scene_path)?;
+    if let Some(n_threads) = options.n_threads {
+        scene_data.settings.threads = n_threads;
+    }
+    if let Some(seed) = options.seed {
+        scene_data.settings.seed = seed;
+    }
+    if let Some(operator) = options.tone_map_operator {
+        scene_data.settings.tone_mapping.operator = operator;
+    }
+    if let Some(exposure) = options.exposure {
+        scene_data.settings.tone_mapping.exposure = exposure;
+    }
+
+    let scene_hash = Checkpoint::scene_hash(&scene_data)?;
+    let mut scene = scene_data.to_scene()?;
+    let checkpoint = match &options.resume_path {
+        Some(path) => Some(Checkpoint::load(
+            Path::new(path),
+            scene_hash,
+            scene.camera.resolution(),
+        )?),
+        None => None,
     };
-    scene.add_object(left_green_wall);
-
-    let right_red_wall = ObjectData {
-        shape: Some(Shape::Cuboid(Vector3::new(10.0, 0.1, 10.0))),
-        position: Some(Isometry::translation(-5.0, -3.0, -5.0)),
-        bsdf: Some(Shader::Lambert(Vector3::new(1.0, 0.0, 0.0))),
-        //bsdf: Some(Shader::Mirror),
-        ..Default::default()
+    let samples_done = checkpoint.as_ref().map_or(0, |c| c.samples_done);
+    match options.n_samples {
+        Some(SampleCount::Total(n_samples)) => scene.settings.samples_per_pixel = n_samples,
+        Some(SampleCount::Additional(n_samples)) => {
+            scene.settings.samples_per_pixel = samples_done + n_samples
+        }
+        None => {
+            if let Some(checkpoint) = &checkpoint {
+                scene.settings.samples_per_pixel = checkpoint.samples_per_pixel;
+            }
+        }
+    }
+    if let Some(checkpoint) = &checkpoint {
+        scene.settings.stratum_samples = Some(checkpoint.sampler_samples);
+    }
+
+    scene.perform_collision_phase();
+    let samples = if options.progressive {
+        render_progressive(options, &scene, scene_hash, checkpoint)?
+    } else {
+        scene.capture()?
     };
-    scene.add_object(right_red_wall);
-
-    //let mesh_data = ObjectData {
-    //shape: Some(Shape::TriMesh(obj_path)),
-    //position: Some(Isometry::translation(0.0, 0.0, -1.0)),
-    //bsdf: Some(Shader::Lambert(Vector3::new(0.8, 0.8, 0.8))),
-    ////bsdf: Some(Shader::Mirror),
-    //..Default::default()
-    //};
-    //scene.add_object(mesh_data);
+    write_image(options, &scene, &samples)?;
+    Ok(())
 }
 
-fn add_lights(scene: &mut SceneData) {
-    //let left_light_data = ObjectData {
-    //shape: Some(Shape::Cuboid(Vector3::new(100.0, 0.1, 100.0))),
-    //position: Some(Isometry::translation(0.0, 20.0, 0.0)),
-    //emission: Some((1.3f32, Point3::new(0.4, 0.4, 0.8))),
-    //..Default::default()
-    //};
-    //scene.add_object(left_light_data);
-
-    let sun_light_data = ObjectData {
-        shape: Some(Shape::Cuboid(Vector3::new(0.3, 0.3, 0.05))),
-        position: Some(Isometry::translation(0.0, 0.0, 2.0)),
-        emission: Some((5.0f32, Vector3::new(1.0, 1.0, 1.0))),
-        ..Default::default()
-    };
-    scene.add_object(sun_light_data);
+fn write_image(
+    options: &RenderOptions,
+    scene: &Scene,
+    samples: &[Vec<Point3<f32>>],
+) -> Result<(), String> {
+    save_image(
+        Path::new(&options.output_path),
+        samples,
+        options.exr_pixel_type,
+        &scene.settings.tone_mapping,
+    )
+    .map_err(|e| format!("cannot write image {}: {}", options.output_path, e))
 }
 
-fn main() {
-    let mut scene_data = SceneData {
-        camera: Some(
-            CameraBuilder::new()
-                .position(Isometry::face_towards(
-                    &Point3::new(5.0, 0.0, 0.0),
-                    &Point3::new(0.0, 0.0, 0.0),
-                    &Vector3::new(0.0, 0.0, 1.0),
-                ))
-                .screen_dimensions(Vector2::new(1.0, 1.0))
-                .resolution(Vector2::new(1000, 1000))
-                .build(),
-        ),
-        objects: Vec::new(),
+fn checkpoint_path(options: &RenderOptions) -> PathBuf {
+    Path::new(&options.output_path).with_extension("ckpt")
+}
+
+// Writes the current linear image and the state of the render, from which it can be resumed.
+// The image is always an OpenEXR file, to keep the radiance whatever the output format.
+fn write_checkpoint(
+    options: &RenderOptions,
+    scene: &Scene,
+    checkpoint: &Checkpoint,
+) -> Result<(), Box<dyn Error>> {
+    let image_path = Path::new(&options.output_path).with_extension("partial.exr");
+    save_image(
+        &image_path,
+        &checkpoint.film.pixels(),
+        options.exr_pixel_type,
+        &scene.settings.tone_mapping,
+    )
+    .map_err(|e| format!("cannot write image {}: {}", image_path.display(), e))?;
+    checkpoint.save(&checkpoint_path(options))?;
+    Ok(())
+}
+
+// Renders one pass at a time until all the samples are done or Ctrl-C is pressed, writing the
+// image and the checkpoint at the requested intervals and at the end.
+fn render_progressive(
+    options: &RenderOptions,
+    scene: &Scene,
+    scene_hash: u64,
+    checkpoint: Option<Checkpoint>,
+) -> Result<Vec<Vec<Point3<f32>>>, Box<dyn Error>> {
+    let interrupted = Arc::new(AtomicBool::new(false));
+    let handler_interrupted = interrupted.clone();
+    ctrlc::set_handler(move || handler_interrupted.store(true, Ordering::SeqCst))?;
+
+    let start_time = Instant::now();
+    let mut last_checkpoint = start_time;
+    let (film, samples_done) = match checkpoint {
+        Some(checkpoint) => (checkpoint.film, checkpoint.samples_done),
+        None => (Film::new(scene.camera.resolution()), 0),
     };
-    let obj_path = "./assets/deer.obj".to_owned();
-    add_objects_to_scene(&mut scene_data, obj_path);
-    add_lights(&mut scene_data);
-
-    //println!("{}", serde_json::to_string_pretty(&scene_data).expect(""));
-    let mut file = File::create("assets/sphere_scene.json").unwrap();
-    file.write_all(
-        serde_json::to_string_pretty(&scene_data)
-            .expect("")
-            .as_bytes(),
+    let mut n_passes = samples_done;
+    let mut result = Ok(());
+    let film = scene.resume_progressive(film, samples_done, |pass, film| {
+        n_passes = pass;
+        if interrupted.load(Ordering::SeqCst) {
+            return false;
+        }
+        let passes_due = options
+            .checkpoint_passes
+            .map_or(false, |passes| pass % passes == 0);
+        let time_due = options
+            .checkpoint_interval
+            .map_or(false, |interval| last_checkpoint.elapsed() >= interval);
+        if (passes_due || time_due) && pass < scene.settings.samples_per_pixel {
+            let checkpoint = Checkpoint::new(scene_hash, pass, &scene.settings, film.clone());
+            result = write_checkpoint(options, scene, &checkpoint);
+            last_checkpoint = Instant::now();
+            println!("Checkpoint after {} passes", pass);
+        }
+        result.is_ok()
+    })?;
+    result?;
+
+    println!(
+        "Rendered {} passes in {} seconds",
+        n_passes - samples_done,
+        start_time.elapsed().as_millis() as f32 / 1000.0
     );
+    let pixels = film.pixels();
+    Checkpoint::new(scene_hash, n_passes, &scene.settings, film).save(&checkpoint_path(options))?;
+    Ok(pixels)
+}
 
-    let mut scene = scene_data.to_scene();
+fn main() {
+    let args = env::args().skip(1).collect::<Vec<_>>();
+    if args.iter().any(|arg| arg == "-h" || arg == "--help") {
+        println!("{}", USAGE);
+        return;
+    }
 
-    scene.perform_collision_phase();
-    let samples = scene.capture(10u32);
-
-    let mut image = RgbImage::new(samples.len() as u32, samples[0].len() as u32);
-    let clamp = |x: f32| 1.0f32.min(0.0f32.max(x));
-    for x in 0..samples.len() {
-        for y in 0..samples[0].len() {
-            let value = samples[x][y];
-
-            image.get_pixel_mut(x as u32, y as u32).data = [
-                (255.0 * clamp(value[0])) as u8,
-                (255.0 * clamp(value[1])) as u8,
-                (255.0 * clamp(value[2])) as u8,
-            ];
+    let options = match parse_args(&args) {
+        Ok(options) => options,
+        Err(message) => {
+            eprintln!("error: {}\n{}", message, USAGE);
+            process::exit(2);
         }
+    };
+
+    if let Err(error) = render(&options) {
+        eprintln!("error: {}", error);
+        process::exit(1);
     }
-    image.save("./results/output_cosine.png").unwrap();
 }
@@ -0,0 +1,88 @@
+use nalgebra::{Point2, Point3, Vector3};
+use ncollide3d::{
+    pipeline::object::CollisionObject,
+    query::{Ray, RayIntersection},
+    shape::{FeatureId, TriMesh},
+};
+
+use crate::object::WorldObjectData;
+
+/// Local description of a surface where a ray hits it.
+#[derive(Clone, Debug)]
+pub struct HitRecord {
+    pub point: Point3<f32>,
+    // Geometric normal, pointing outside of the object.
+    pub normal: Vector3<f32>,
+    // Normal used to shade the surface, on the same side as the geometric normal.
+    pub shading_normal: Vector3<f32>,
+    pub uv: Point2<f32>,
+    // Index of the triangle hit, for triangle meshes.
+    pub face: Option<usize>,
+}
+
+impl HitRecord {
+    /// Describes the intersection of `ray` with `object`, as returned by `Scene::intersect`.
+    pub fn new(
+        ray: &Ray<f32>,
+        object: &CollisionObject<f32, WorldObjectData>,
+        inter: &RayIntersection<f32>,
+    ) -> Self {
+        let point = ray.point_at(inter.toi);
+        let normal = inter.normal;
+        let mesh = object.shape().as_shape::<TriMesh<f32>>();
+        let face = match (mesh, inter.feature) {
+            // Back faces are numbered after the front faces.
+            (Some(mesh), FeatureId::Face(i)) => Some(i % mesh.faces().len()),
+            _ => None,
+        };
+        let shading_normal = match (&object.data().vertex_normals, mesh, face) {
+            (Some(vertex_normals), Some(mesh), Some(face)) => {
+                let indices = mesh.faces()[face].indices;
+                let points = mesh.points();
+                let weights = barycentric_coordinates(
+                    &object.position().inverse_transform_point(&point),
+                    &points[indices[0]],
+                    &points[indices[1]],
+                    &points[indices[2]],
+                );
+                let local_normal = weights[0] * vertex_normals[indices[0]]
+                    + weights[1] * vertex_normals[indices[1]]
+                    + weights[2] * vertex_normals[indices[2]];
+                let shading_normal = object.position() * local_normal;
+                match shading_normal.try_normalize(1e-6) {
+                    Some(n) if n.dot(&normal) < 0.0 => -n,
+                    Some(n) => n,
+                    None => normal,
+                }
+            }
+            _ => normal,
+        };
+
+        HitRecord {
+            point,
+            normal,
+            shading_normal,
+            uv: inter.uvs.unwrap_or_else(Point2::origin),
+            face,
+        }
+    }
+}
+
+// Weights of the vertices of the triangle abc giving the projection of p on its plane.
+fn barycentric_coordinates(
+    p: &Point3<f32>,
+    a: &Point3<f32>,
+    b: &Point3<f32>,
+    c: &Point3<f32>,
+) -> Vector3<f32> {
+    let (ab, ac, ap) = (b - a, c - a, p - a);
+    let (d00, d01, d11) = (ab.dot(&ab), ab.dot(&ac), ac.dot(&ac));
+    let (d20, d21) = (ap.dot(&ab), ap.dot(&ac));
+    let denominator = d00 * d11 - d01 * d01;
+    if denominator == 0.0 {
+        return Vector3::new(1.0, 0.0, 0.0);
+    }
+    let v = (d11 * d20 - d01 * d21) / denominator;
+    let w = (d00 * d21 - d01 * d20) / denominator;
+    Vector3::new(1.0 - v - w, v, w)
+}
@@ -1,4 +1,6 @@
+mod hit_record;
 mod object_data;
+pub use hit_record::HitRecord;
 pub use object_data::*;
 
 pub mod shapes;
@@ -1,7 +1,9 @@
 use nalgebra::{Isometry3, Point3, Vector3};
 use serde::{Deserialize, Serialize};
 
-use crate::object::shapes::Shape;
+use crate::error::Result;
+use crate::object::shapes::{MeshShading, Shape};
+use crate::object::HitRecord;
 use crate::shaders::{Shader, BSDF};
 
 #[derive(Default, Serialize, Deserialize)]
@@ -13,14 +15,17 @@ pub struct ObjectData {
 }
 
 impl ObjectData {
-    pub fn to_world_data(self) -> WorldObjectData {
-        WorldObjectData {
+    pub fn to_world_data(self) -> Result<WorldObjectData> {
+        Ok(WorldObjectData {
             emission: self.emission,
             bsdf: match self.bsdf {
-                Some(shader) => Some(shader.to_bsdf()),
+                Some(shader) => Some(shader.to_bsdf()?),
                 None => None,
             },
-        }
+            vertex_normals: None,
+            materials: Vec::new(),
+            face_materials: Vec::new(),
+        })
     }
 }
 
@@ -28,4 +33,59 @@ impl ObjectData {
 pub struct WorldObjectData {
     pub emission: Option<(f32, Vector3<f32>)>,
     pub bsdf: Option<Box<dyn BSDF>>,
+    // Normals at the vertices of a triangle mesh, for smooth shading.
+    pub vertex_normals: Option<Vec<Vector3<f32>>>,
+    // Materials of a triangle mesh, used by the faces when the object has no emission or BSDF
+    // of its own.
+    pub materials: Vec<WorldMaterial>,
+    pub face_materials: Vec<Option<usize>>,
+}
+
+pub struct WorldMaterial {
+    pub emission: Option<(f32, Vector3<f32>)>,
+    pub bsdf: Option<Box<dyn BSDF>>,
+}
+
+impl WorldObjectData {
+    pub fn set_shading(&mut self, shading: MeshShading) -> Result<()> {
+        self.vertex_normals = shading.vertex_normals;
+        self.materials = shading
+            .materials
+            .into_iter()
+            .map(|material| {
+                Ok(WorldMaterial {
+                    emission: material.emission,
+                    bsdf: match material.bsdf {
+                        Some(shader) => Some(shader.to_bsdf()?),
+                        None => None,
+                    },
+                })
+            })
+            .collect::<Result<_>>()?;
+        self.face_materials = shading.face_materials;
+        Ok(())
+    }
+
+    /// Index in `materials` of the material of the face at `hit`.
+    pub fn material_index(&self, hit: &HitRecord) -> Option<usize> {
+        *self.face_materials.get(hit.face?)?
+    }
+
+    fn material(&self, hit: &HitRecord) -> Option<&WorldMaterial> {
+        self.materials.get(self.material_index(hit)?)
+    }
+
+    /// Emission of the surface at `hit`.
+    pub fn emission_at(&self, hit: &HitRecord) -> Option<&(f32, Vector3<f32>)> {
+        self.emission
+            .as_ref()
+            .or_else(|| self.material(hit)?.emission.as_ref())
+    }
+
+    /// BSDF of the surface at `hit`.
+    pub fn bsdf_at(&self, hit: &HitRecord) -> Option<&dyn BSDF> {
+        self.bsdf
+            .as_deref()
+            .or_else(|| self.material(hit)?.bsdf.as_deref())
+    }
 }
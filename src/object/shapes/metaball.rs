@@ -2,32 +2,106 @@ use nalgebra::{Isometry3, Point3, Unit, Vector3};
 use ncollide3d::{bounding_volume, query, shape};
 use serde::{Deserialize, Serialize};
 
+const MAX_MARCHING_STEPS: usize = 1024;
+const BISECTION_STEPS: usize = 24;
+
+/// Kernel giving the contribution of a point to the field, with `x = r² / radius²`.
+///
+/// Every kernel is 1 at the center of a point and smoothly reaches 0 at `radius`.
+#[derive(Clone, Copy, Debug, Default, Serialize, Deserialize)]
+pub enum Falloff {
+    // Wyvill's soft object kernel (1 - x)³.
+    #[default]
+    Cubic,
+    // (1 - x)², slightly blobbier than the cubic kernel.
+    Quadratic,
+    // Blinn's exponential kernel, shifted to vanish at the radius.
+    Gaussian,
+}
+
+impl Falloff {
+    fn value(self, x: f32) -> f32 {
+        let y = 1.0 - x;
+        match self {
+            Falloff::Cubic => y * y * y,
+            Falloff::Quadratic => y * y,
+            Falloff::Gaussian => ((-4.0 * x).exp() - (-4.0f32).exp()) / (1.0 - (-4.0f32).exp()),
+        }
+    }
+
+    // Derivative of the kernel with respect to x.
+    fn derivative(self, x: f32) -> f32 {
+        let y = 1.0 - x;
+        match self {
+            Falloff::Cubic => -3.0 * y * y,
+            Falloff::Quadratic => -2.0 * y,
+            Falloff::Gaussian => -4.0 * (-4.0 * x).exp() / (1.0 - (-4.0f32).exp()),
+        }
+    }
+
+    // Upper bound of |df/dr| for a unit radius, used to bound the marching steps.
+    fn max_slope(self) -> f32 {
+        match self {
+            Falloff::Cubic => 1.72,
+            Falloff::Quadratic => 1.54,
+            Falloff::Gaussian => 1.75,
+        }
+    }
+}
+
 #[derive(Clone, Serialize, Deserialize)]
 pub struct Metaball {
     points: Vec<Point3<f32>>,
     radius: f32,
     level: f32,
+    falloff: Falloff,
 }
 
 impl Metaball {
-    pub fn new(points: Vec<Point3<f32>>, radius: f32, level: f32) -> Self {
+    pub fn new(points: Vec<Point3<f32>>, radius: f32, level: f32, falloff: Falloff) -> Self {
         Metaball {
             points,
             radius,
             level,
+            falloff,
         }
     }
-}
 
-impl shape::Shape<f32> for Metaball {
-    fn aabb(&self, m: &Isometry3<f32>) -> bounding_volume::AABB<f32> {
+    // Field minus the iso-level, positive inside the surface.
+    fn field(&self, points: &[&Point3<f32>], p: &Point3<f32>) -> f32 {
+        let inv_radius_2 = 1.0 / (self.radius * self.radius);
+        let mut value = -self.level;
+        for center in points {
+            let x = (p - *center).norm_squared() * inv_radius_2;
+            if x < 1.0 {
+                value += self.falloff.value(x);
+            }
+        }
+        value
+    }
+
+    fn gradient(&self, p: &Point3<f32>) -> Vector3<f32> {
+        let inv_radius_2 = 1.0 / (self.radius * self.radius);
+        let mut gradient = Vector3::new(0.0, 0.0, 0.0);
+        for center in &self.points {
+            let offset = p - center;
+            let x = offset.norm_squared() * inv_radius_2;
+            if x < 1.0 {
+                gradient += self.falloff.derivative(x) * 2.0 * inv_radius_2 * offset;
+            }
+        }
+        gradient
+    }
+
+    fn bounding_box(&self, m: &Isometry3<f32>) -> Option<bounding_volume::AABB<f32>> {
         let transformed_points = self
             .points
             .iter()
             .map(|p| m.transform_point(p))
             .collect::<Vec<_>>();
+        let first_point = transformed_points.first()?;
         let (min_point, max_point) = transformed_points.iter().fold(
-            (transformed_points[0].clone(), transformed_points[0].clone()),
+            (*first_point, *first_point),
             |(min_point, max_point), p| {
                 let new_min_point = Point3::new(
                     min_point[0].min(p[0]),
@@ -45,7 +119,15 @@ impl shape::Shape<f32> for Metaball {
         let one_vector = Vector3::new(1.0, 1.0, 1.0);
         let aabb_min = min_point - self.radius * one_vector;
         let aabb_max = max_point + self.radius * one_vector;
-        bounding_volume::AABB::new(aabb_min, aabb_max)
+        Some(bounding_volume::AABB::new(aabb_min, aabb_max))
+    }
+}
+
+impl shape::Shape<f32> for Metaball {
+    fn aabb(&self, m: &Isometry3<f32>) -> bounding_volume::AABB<f32> {
+        let origin = Point3::from(m.translation.vector);
+        self.bounding_box(m)
+            .unwrap_or_else(|| bounding_volume::AABB::new(origin, origin))
     }
 
     fn tangent_cone_contains_dir(
@@ -57,6 +139,10 @@ impl shape::Shape<f32> for Metaball {
     ) -> bool {
         false
     }
+
+    fn as_ray_cast(&self) -> Option<&dyn query::RayCast<f32>> {
+        Some(self)
+    }
 }
 
 impl query::RayCast<f32> for Metaball {
@@ -67,6 +153,96 @@ impl query::RayCast<f32> for Metaball {
         max_toi: f32,
         solid: bool,
     ) -> Option<query::RayIntersection<f32>> {
+        let local_ray = ray.inverse_transform_by(m);
+        let (start_toi, end_toi) = self
+            .bounding_box(&Isometry3::identity())?
+            .clip_ray_parameters(&local_ray)?;
+        let end_toi = end_toi.min(max_toi);
+
+        // Only the points whose support is crossed by the ray contribute to the field.
+        let dir_norm_2 = local_ray.dir.norm_squared();
+        let radius_2 = self.radius * self.radius;
+        let points = self
+            .points
+            .iter()
+            .filter(|p| {
+                let offset = *p - local_ray.origin;
+                let t = (offset.dot(&local_ray.dir) / dir_norm_2).max(0.0);
+                (local_ray.point_at(t) - *p).norm_squared() < radius_2
+            })
+            .collect::<Vec<_>>();
+        if points.is_empty() {
+            return None;
+        }
+
+        // Sphere tracing: within `reach` of a position, only the points closer than
+        // `radius + reach` contribute to the field, and each of them changes it by at most
+        // max_slope / radius per unit of length. A step of |field| / lipschitz, bounded by
+        // `reach`, can then never cross the surface.
+        let dir_norm = dir_norm_2.sqrt();
+        let reach = self.radius;
+        let near_distance_2 = (self.radius + reach) * (self.radius + reach);
+        let slope = self.falloff.max_slope() / self.radius * dir_norm;
+        let max_step = reach / dir_norm;
+        let mut min_step = 1.0e-4 * self.radius / dir_norm;
+
+        let mut toi = start_toi;
+        let mut value = self.field(&points, &local_ray.point_at(toi));
+        let inside = value > 0.0;
+        if inside && solid {
+            return Some(query::RayIntersection::new(
+                toi,
+                Vector3::new(0.0, 0.0, 0.0),
+                shape::FeatureId::Face(0),
+            ));
+        }
+
+        for step in 0.. {
+            if toi >= end_toi {
+                return None;
+            }
+            // Past the budget of steps, march with steps large enough to reach the end of the
+            // ray within the same budget, relying on the bisection to find the crossing.
+            if step == MAX_MARCHING_STEPS {
+                min_step = min_step.max((end_toi - toi) / MAX_MARCHING_STEPS as f32);
+            }
+            let position = local_ray.point_at(toi);
+            let n_near = points
+                .iter()
+                .filter(|p| (position - **p).norm_squared() < near_distance_2)
+                .count();
+            let lipschitz = n_near as f32 * slope;
+            let next_toi = if lipschitz > 0.0 {
+                toi + (value.abs() / lipschitz).min(max_step).max(min_step)
+            } else {
+                toi + max_step
+            }
+            .min(end_toi);
+            let next_value = self.field(&points, &local_ray.point_at(next_toi));
+
+            if (next_value > 0.0) != inside {
+                // Refine the crossing by bisection.
+                let (mut low, mut high) = (toi, next_toi);
+                for _ in 0..BISECTION_STEPS {
+                    let middle = 0.5 * (low + high);
+                    if (self.field(&points, &local_ray.point_at(middle)) > 0.0) == inside {
+                        low = middle;
+                    } else {
+                        high = middle;
+                    }
+                }
+                let local_normal = -self.gradient(&local_ray.point_at(high));
+                let normal = m * local_normal.try_normalize(1.0e-12)?;
+                return Some(query::RayIntersection::new(
+                    high,
+                    normal,
+                    shape::FeatureId::Face(0),
+                ));
+            }
+
+            toi = next_toi;
+            value = next_value;
+        }
         None
     }
 }
@@ -1,6 +1,8 @@
 use nalgebra::{Point2, Point3, Vector3};
 use ncollide3d::shape::ShapeHandle;
-use serde::{Deserialize, Serialize};
+use serde::{Deserialize, Deserializer, Serialize};
+
+use crate::error::{Error, Result};
 
 pub trait ObjectToShape {
     type ShapeType;
@@ -16,25 +18,102 @@ mod cuboid;
 mod metaball;
 mod trimesh;
 
+pub use metaball::Falloff;
+pub use trimesh::MeshShading;
+
+fn default_metaball_radius() -> f32 {
+    1.0
+}
+
+fn default_metaball_level() -> f32 {
+    0.5
+}
+
 #[derive(Serialize, Deserialize)]
 pub enum Shape {
     TriMesh(String),
     Cuboid(Vector3<f32>),
     Ball(f32),
-    Metaball(Vec<Point3<f32>>),
+    #[serde(deserialize_with = "deserialize_metaball")]
+    Metaball {
+        points: Vec<Point3<f32>>,
+        radius: f32,
+        level: f32,
+        falloff: Falloff,
+    },
+}
+
+// Metaballs are either described by their parameters, or by their points alone as in older
+// scene files.
+#[derive(Deserialize)]
+#[serde(untagged)]
+enum MetaballData {
+    Points(Vec<Point3<f32>>),
+    Parameters {
+        points: Vec<Point3<f32>>,
+        #[serde(default = "default_metaball_radius")]
+        radius: f32,
+        #[serde(default = "default_metaball_level")]
+        level: f32,
+        #[serde(default)]
+        falloff: Falloff,
+    },
+}
+
+// Points, radius, level and falloff of a metaball.
+type MetaballFields = (Vec<Point3<f32>>, f32, f32, Falloff);
+
+fn deserialize_metaball<'de, D>(deserializer: D) -> std::result::Result<MetaballFields, D::Error>
+where
+    D: Deserializer<'de>,
+{
+    Ok(match MetaballData::deserialize(deserializer)? {
+        MetaballData::Points(points) => (
+            points,
+            default_metaball_radius(),
+            default_metaball_level(),
+            Falloff::default(),
+        ),
+        MetaballData::Parameters {
+            points,
+            radius,
+            level,
+            falloff,
+        } => (points, radius, level, falloff),
+    })
 }
 
 impl Shape {
-    pub fn get_handle(self) -> ShapeHandle<f32> {
-        match self {
+    /// Collision shape, along with the shading attributes of triangle meshes.
+    pub fn get_handle(self) -> Result<(ShapeHandle<f32>, MeshShading)> {
+        Ok(match self {
             Shape::TriMesh(obj_path) => {
-                ShapeHandle::new(trimesh::TriMesh::new(obj_path).to_shape())
+                let mut mesh = trimesh::TriMesh::new(obj_path)?;
+                let shading = mesh.take_shading();
+                (ShapeHandle::new(mesh.to_shape()), shading)
             }
-            Shape::Cuboid(dims) => ShapeHandle::new(cuboid::Cuboid::new(dims).to_shape()),
-            Shape::Ball(radius) => ShapeHandle::new(ball::Ball::new(radius).to_shape()),
-            Shape::Metaball(points) => {
-                ShapeHandle::new(metaball::Metaball::new(points, 1.0f32, 1.0f32))
+            Shape::Cuboid(dims) => (
+                ShapeHandle::new(cuboid::Cuboid::new(dims).to_shape()),
+                MeshShading::default(),
+            ),
+            Shape::Ball(radius) => (
+                ShapeHandle::new(ball::Ball::new(radius).to_shape()),
+                MeshShading::default(),
+            ),
+            Shape::Metaball { radius, .. } if radius.is_nan() || radius <= 0.0 => {
+                return Err(Error::UnsupportedShape {
+                    message: format!("metaball radius must be positive, not {}", radius),
+                })
             }
-        }
+            Shape::Metaball {
+                points,
+                radius,
+                level,
+                falloff,
+            } => (
+                ShapeHandle::new(metaball::Metaball::new(points, radius, level, falloff)),
+                MeshShading::default(),
+            ),
+        })
     }
 }
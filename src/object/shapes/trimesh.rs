@@ -1,43 +1,187 @@
+use std::collections::HashMap;
 use std::path::Path;
 
-use nalgebra::Point3;
+use nalgebra::{Point2, Point3, Vector2, Vector3};
 use ncollide3d::shape;
-use obj::{Obj, SimplePolygon};
+use obj::{IndexTuple, Material, Obj, SimplePolygon};
 use serde::{Deserialize, Serialize};
 
+use crate::error::{Error, Result};
 use crate::object::shapes::ObjectToShape;
+use crate::shaders::{ConductorMaterial, Shader};
+use crate::textures::{ColorData, TextureData, WrapMode};
+
+/// Material of some faces of a mesh, read from an MTL file.
+#[derive(Serialize, Deserialize)]
+pub struct MeshMaterial {
+    pub emission: Option<(f32, Vector3<f32>)>,
+    pub bsdf: Option<Shader>,
+}
+
+impl MeshMaterial {
+    // Maps the Phong model of MTL files to the closest shader: transparent materials are
+    // dielectrics, purely specular ones conductors and the others diffuse. The Phong exponent
+    // gives the roughness of the glossy shaders.
+    fn new(material: &Material, directory: &Path) -> Self {
+        let black = [0.0; 3];
+        let kd = material.kd.unwrap_or(black);
+        let ks = material.ks.unwrap_or(black);
+        let roughness = material
+            .ns
+            .map_or(0.0, |ns| (2.0 / (ns.max(0.0) + 2.0)).sqrt());
+
+        let transparent = match material.d {
+            Some(d) => d < 1.0,
+            None => false,
+        };
+        let bsdf = if transparent {
+            Some(Shader::Dielectric {
+                ior: material.ni.filter(|&ni| ni > 1.0).unwrap_or(1.5),
+                roughness,
+            })
+        } else if let Some(path) = &material.map_kd {
+            Some(Shader::Lambert(ColorData::Texture(TextureData::Image {
+                path: directory.join(path).to_string_lossy().into_owned(),
+                wrap: WrapMode::default(),
+                scale: Vector2::new(1.0, 1.0),
+            })))
+        } else if kd == black && ks != black {
+            // Index of refraction giving the specular color as reflectance at normal incidence.
+            let eta = Vector3::from_column_slice(&ks).map(|r| {
+                let r = r.max(0.0).min(0.999).sqrt();
+                (1.0 + r) / (1.0 - r)
+            });
+            Some(Shader::Conductor {
+                material: ConductorMaterial::Custom {
+                    eta,
+                    k: Vector3::new(0.0, 0.0, 0.0),
+                },
+                roughness,
+            })
+        } else if material.kd.is_some() {
+            Some(Shader::Lambert(ColorData::Constant(
+                Vector3::from_column_slice(&kd),
+            )))
+        } else {
+            None
+        };
+
+        let emission = material
+            .ke
+            .filter(|&ke| ke != black)
+            .map(|ke| (1.0, Vector3::from_column_slice(&ke)));
+        MeshMaterial { emission, bsdf }
+    }
+}
 
 #[derive(Serialize, Deserialize)]
 pub struct TriMesh {
     vertices: Vec<Point3<f32>>,
     indices: Vec<Point3<usize>>,
+    uvs: Option<Vec<Point2<f32>>>,
+    normals: Option<Vec<Vector3<f32>>>,
+    materials: Vec<MeshMaterial>,
+    // Index in `materials` of the material of each triangle, if it has one.
+    face_materials: Vec<Option<usize>>,
 }
 
 impl TriMesh {
-    pub fn new(path: String) -> Self {
-        let mesh = Obj::<SimplePolygon>::load(Path::new(&path)).unwrap();
-        let vertices = mesh
-            .position
-            .iter()
-            .map(|p| Point3::from_slice(p))
-            .collect::<Vec<_>>();
+    pub fn new(path: String) -> Result<Self> {
+        let path = Path::new(&path);
+        let mut mesh = Obj::<SimplePolygon>::load(path).map_err(|e| Error::from_io(path, e))?;
+        if let Err(mut errors) = mesh.load_mtls() {
+            let (mtl_path, error) = errors.swap_remove(0);
+            return Err(Error::from_io(&mesh.path.join(mtl_path), error));
+        }
+
+        // OBJ faces index positions, texture coordinates and normals separately, each distinct
+        // combination becomes a vertex of the mesh.
+        let mut vertex_indices = HashMap::new();
+        let mut tuples = Vec::new();
         let mut indices = Vec::new();
-        for object in mesh.objects {
-            for group in object.groups {
-                for poly in group.polys {
-                    let point = Point3::new(poly[0].0, poly[1].0, poly[2].0);
-                    indices.push(point);
+        let mut material_indices = HashMap::new();
+        let mut materials = Vec::new();
+        let mut face_materials = Vec::new();
+        for object in &mesh.objects {
+            for group in &object.groups {
+                let material = group.material.as_ref().map(|material| {
+                    *material_indices
+                        .entry(material.name.clone())
+                        .or_insert_with(|| {
+                            materials.push(MeshMaterial::new(material, &mesh.path));
+                            materials.len() - 1
+                        })
+                });
+                for poly in &group.polys {
+                    if poly.len() < 3 {
+                        return Err(Error::Parse {
+                            path: path.to_owned(),
+                            message: "face with less than 3 vertices".to_owned(),
+                        });
+                    }
+                    let mut poly_indices = poly.iter().map(|tuple| {
+                        let IndexTuple(p, t, n) = *tuple;
+                        *vertex_indices.entry((p, t, n)).or_insert_with(|| {
+                            tuples.push((p, t, n));
+                            tuples.len() - 1
+                        })
+                    });
+                    let first = poly_indices.next().unwrap();
+                    let mut previous = poly_indices.next().unwrap();
+                    for index in poly_indices {
+                        indices.push(Point3::new(first, previous, index));
+                        face_materials.push(material);
+                        previous = index;
+                    }
                 }
             }
         }
-        TriMesh { vertices, indices }
+
+        let vertices = tuples
+            .iter()
+            .map(|&(p, _, _)| Point3::from_slice(&mesh.position[p]))
+            .collect();
+        // Texture coordinates and normals are only used if all the vertices have one.
+        let uvs = tuples
+            .iter()
+            .map(|&(_, t, _)| t.map(|t| Point2::from_slice(&mesh.texture[t])))
+            .collect();
+        let normals = tuples
+            .iter()
+            .map(|&(_, _, n)| n.map(|n| Vector3::from_column_slice(&mesh.normal[n]).normalize()))
+            .collect();
+        Ok(TriMesh {
+            vertices,
+            indices,
+            uvs,
+            normals,
+            materials,
+            face_materials,
+        })
+    }
+
+    /// Shading attributes of the mesh, which are not part of the collision shape.
+    pub fn take_shading(&mut self) -> MeshShading {
+        MeshShading {
+            vertex_normals: self.normals.take(),
+            materials: std::mem::take(&mut self.materials),
+            face_materials: std::mem::take(&mut self.face_materials),
+        }
     }
 }
 
+/// Per-vertex normals and per-face materials of a triangle mesh.
+#[derive(Default)]
+pub struct MeshShading {
+    pub vertex_normals: Option<Vec<Vector3<f32>>>,
+    pub materials: Vec<MeshMaterial>,
+    pub face_materials: Vec<Option<usize>>,
+}
+
 impl ObjectToShape for TriMesh {
     type ShapeType = shape::TriMesh<f32>;
 
     fn to_shape(self) -> Self::ShapeType {
-        shape::TriMesh::new(self.vertices, self.indices, None)
+        shape::TriMesh::new(self.vertices, self.indices, self.uvs)
     }
 }
@@ -0,0 +1,185 @@
+use std::io::{self, Write};
+
+use nalgebra::Point3;
+
+pub(crate) const MAGIC_NUMBER: [u8; 4] = [0x76, 0x2f, 0x31, 0x01];
+// Version 2, single-part scanline file with short attribute names.
+const VERSION: [u8; 4] = [2, 0, 0, 0];
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum ExrPixelType {
+    Half,
+    Float,
+}
+
+impl ExrPixelType {
+    fn id(self) -> i32 {
+        match self {
+            ExrPixelType::Half => 1,
+            ExrPixelType::Float => 2,
+        }
+    }
+
+    fn size(self) -> usize {
+        match self {
+            ExrPixelType::Half => 2,
+            ExrPixelType::Float => 4,
+        }
+    }
+}
+
+/// Converts from IEEE 754 half precision.
+pub(crate) fn f16_to_f32(half: u16) -> f32 {
+    let sign = ((half & 0x8000) as u32) << 16;
+    let exponent = ((half >> 10) & 0x1f) as u32;
+    let mantissa = (half & 0x03ff) as u32;
+
+    let bits = match exponent {
+        0 if mantissa == 0 => sign,
+        0 => {
+            // Subnormal half, normalized in single precision.
+            let shift = mantissa.leading_zeros() - 21;
+            let mantissa = (mantissa << shift) & 0x03ff;
+            sign | ((127 - 15 + 1 - shift) << 23) | (mantissa << 13)
+        }
+        0x1f => sign | 0x7f80_0000 | (mantissa << 13),
+        _ => sign | ((exponent + 127 - 15) << 23) | (mantissa << 13),
+    };
+    f32::from_bits(bits)
+}
+
+/// Converts to IEEE 754 half precision, rounding to the nearest even value.
+fn f32_to_f16(value: f32) -> u16 {
+    let bits = value.to_bits();
+    let sign = ((bits >> 16) & 0x8000) as u16;
+    let exponent = ((bits >> 23) & 0xff) as i32;
+    let mantissa = bits & 0x007f_ffff;
+
+    if exponent == 0xff {
+        // Infinity, or NaN with a mantissa bit kept set.
+        let nan_bit = if mantissa != 0 { 0x0200 } else { 0 };
+        return sign | 0x7c00 | nan_bit;
+    }
+
+    let half_exponent = exponent - 127 + 15;
+    if half_exponent >= 0x1f {
+        return sign | 0x7c00;
+    }
+    if half_exponent <= 0 {
+        if half_exponent < -10 {
+            return sign;
+        }
+        // Subnormal half, the implicit leading bit becomes explicit.
+        let mantissa = mantissa | 0x0080_0000;
+        let shift = (14 - half_exponent) as u32;
+        let half_mantissa = mantissa >> shift;
+        let remainder = mantissa & ((1 << shift) - 1);
+        let halfway = 1 << (shift - 1);
+        let round_up = remainder > halfway || (remainder == halfway && half_mantissa & 1 == 1);
+        return sign | (half_mantissa + round_up as u32) as u16;
+    }
+
+    let half = ((half_exponent as u32) << 10) | (mantissa >> 13);
+    let remainder = mantissa & 0x1fff;
+    let round_up = remainder > 0x1000 || (remainder == 0x1000 && half & 1 == 1);
+    // A carry out of the mantissa correctly bumps the exponent, up to infinity.
+    sign | (half + round_up as u32) as u16
+}
+
+fn write_attribute<W: Write>(
+    writer: &mut W,
+    name: &str,
+    attribute_type: &str,
+    value: &[u8],
+) -> io::Result<()> {
+    writer.write_all(name.as_bytes())?;
+    writer.write_all(&[0])?;
+    writer.write_all(attribute_type.as_bytes())?;
+    writer.write_all(&[0])?;
+    writer.write_all(&(value.len() as i32).to_le_bytes())?;
+    writer.write_all(value)
+}
+
+fn box2i(width: usize, height: usize) -> Vec<u8> {
+    [0, 0, width as i32 - 1, height as i32 - 1]
+        .iter()
+        .flat_map(|v| v.to_le_bytes().to_vec())
+        .collect()
+}
+
+/// Writes an uncompressed scanline OpenEXR image with linear R, G and B channels.
+pub fn write_exr<W: Write>(
+    writer: &mut W,
+    samples: &[Vec<Point3<f32>>],
+    pixel_type: ExrPixelType,
+) -> io::Result<()> {
+    let width = samples.len();
+    let height = samples[0].len();
+
+    writer.write_all(&MAGIC_NUMBER)?;
+    writer.write_all(&VERSION)?;
+
+    // Channels are stored in alphabetical order.
+    let channels = [("B", 2), ("G", 1), ("R", 0)];
+    let mut channel_list = Vec::new();
+    for (name, _) in channels.iter() {
+        channel_list.extend_from_slice(name.as_bytes());
+        channel_list.push(0);
+        channel_list.extend_from_slice(&pixel_type.id().to_le_bytes());
+        // pLinear and reserved bytes.
+        channel_list.extend_from_slice(&[0, 0, 0, 0]);
+        // x and y sampling.
+        channel_list.extend_from_slice(&1i32.to_le_bytes());
+        channel_list.extend_from_slice(&1i32.to_le_bytes());
+    }
+    channel_list.push(0);
+
+    let mut header = Vec::new();
+    write_attribute(&mut header, "channels", "chlist", &channel_list)?;
+    write_attribute(&mut header, "compression", "compression", &[0])?;
+    write_attribute(&mut header, "dataWindow", "box2i", &box2i(width, height))?;
+    write_attribute(&mut header, "displayWindow", "box2i", &box2i(width, height))?;
+    write_attribute(&mut header, "lineOrder", "lineOrder", &[0])?;
+    write_attribute(
+        &mut header,
+        "pixelAspectRatio",
+        "float",
+        &1.0f32.to_le_bytes(),
+    )?;
+    let window_center = [0.0f32.to_le_bytes(), 0.0f32.to_le_bytes()].concat();
+    write_attribute(&mut header, "screenWindowCenter", "v2f", &window_center)?;
+    write_attribute(
+        &mut header,
+        "screenWindowWidth",
+        "float",
+        &1.0f32.to_le_bytes(),
+    )?;
+    header.push(0);
+    writer.write_all(&header)?;
+
+    // Each uncompressed chunk holds a single scanline.
+    let line_size = channels.len() * width * pixel_type.size();
+    let chunk_size = (8 + line_size) as u64;
+    let first_chunk = (MAGIC_NUMBER.len() + VERSION.len() + header.len() + 8 * height) as u64;
+    for y in 0..height as u64 {
+        writer.write_all(&(first_chunk + y * chunk_size).to_le_bytes())?;
+    }
+
+    let mut line = Vec::with_capacity(line_size);
+    for y in 0..height {
+        line.clear();
+        for (_, c) in channels.iter() {
+            for column in samples {
+                let value = column[y][*c];
+                match pixel_type {
+                    ExrPixelType::Half => line.extend_from_slice(&f32_to_f16(value).to_le_bytes()),
+                    ExrPixelType::Float => line.extend_from_slice(&value.to_le_bytes()),
+                }
+            }
+        }
+        writer.write_all(&(y as i32).to_le_bytes())?;
+        writer.write_all(&(line_size as i32).to_le_bytes())?;
+        writer.write_all(&line)?;
+    }
+    Ok(())
+}
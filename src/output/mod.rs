@@ -0,0 +1,61 @@
+use std::fs::File;
+use std::io::{self, BufWriter, Write};
+use std::path::Path;
+
+use image::RgbImage;
+use nalgebra::Point3;
+
+pub(crate) mod exr;
+mod pfm;
+mod rgbe;
+mod tone_mapping;
+
+pub use exr::{write_exr, ExrPixelType};
+pub use pfm::write_pfm;
+pub use rgbe::write_rgbe;
+pub use tone_mapping::*;
+
+/// Saves a float buffer indexed as `samples[x][y]`, with `y = 0` at the top of the image.
+///
+/// The format is chosen from the file extension: `.exr`, `.pfm` and `.hdr` keep the linear
+/// radiance values, any other extension is tone mapped and written as an 8-bit sRGB image.
+pub fn save_image(
+    path: &Path,
+    samples: &[Vec<Point3<f32>>],
+    exr_pixel_type: ExrPixelType,
+    tone_mapping: &ToneMapping,
+) -> io::Result<()> {
+    let extension = path
+        .extension()
+        .and_then(|e| e.to_str())
+        .map(|e| e.to_lowercase());
+    match extension.as_deref() {
+        Some("exr") => write_hdr(path, |w| write_exr(w, samples, exr_pixel_type)),
+        Some("pfm") => write_hdr(path, |w| write_pfm(w, samples)),
+        Some("hdr") => write_hdr(path, |w| write_rgbe(w, samples)),
+        _ => save_ldr(path, samples, tone_mapping),
+    }
+}
+
+fn write_hdr<F>(path: &Path, write: F) -> io::Result<()>
+where
+    F: FnOnce(&mut BufWriter<File>) -> io::Result<()>,
+{
+    let mut writer = BufWriter::new(File::create(path)?);
+    write(&mut writer)?;
+    writer.flush()
+}
+
+fn save_ldr(
+    path: &Path,
+    samples: &[Vec<Point3<f32>>],
+    tone_mapping: &ToneMapping,
+) -> io::Result<()> {
+    let mut image = RgbImage::new(samples.len() as u32, samples[0].len() as u32);
+    for (x, column) in samples.iter().enumerate() {
+        for (y, value) in column.iter().enumerate() {
+            image.get_pixel_mut(x as u32, y as u32).data = tone_mapping.to_srgb8(value);
+        }
+    }
+    image.save(path)
+}
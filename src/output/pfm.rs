@@ -0,0 +1,21 @@
+use std::io::{self, Write};
+
+use nalgebra::Point3;
+
+/// Writes a little-endian colour Portable Float Map.
+pub fn write_pfm<W: Write>(writer: &mut W, samples: &[Vec<Point3<f32>>]) -> io::Result<()> {
+    let width = samples.len();
+    let height = samples[0].len();
+    // A negative scale marks little-endian data.
+    write!(writer, "PF\n{} {}\n-1.0\n", width, height)?;
+
+    // Scanlines are stored from the bottom of the image to the top.
+    for y in (0..height).rev() {
+        for column in samples {
+            for value in column[y].iter() {
+                writer.write_all(&value.to_le_bytes())?;
+            }
+        }
+    }
+    Ok(())
+}
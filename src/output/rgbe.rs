@@ -0,0 +1,109 @@
+use std::io::{self, Write};
+
+use nalgebra::Point3;
+
+const MIN_RLE_WIDTH: usize = 8;
+const MAX_RLE_WIDTH: usize = 0x7fff;
+const MIN_RUN_LENGTH: usize = 4;
+const MAX_RUN_LENGTH: usize = 127;
+
+fn to_rgbe(value: &Point3<f32>) -> [u8; 4] {
+    let max_value = value[0].max(value[1]).max(value[2]);
+    if max_value.is_nan() || max_value <= 1e-32 {
+        return [0, 0, 0, 0];
+    }
+    // max_value = mantissa * 2^exponent with mantissa in [0.5, 1).
+    let mut exponent = max_value.log2().floor() as i32 + 1;
+    let mut mantissa = max_value / 2.0f32.powi(exponent);
+    if mantissa >= 1.0 {
+        mantissa *= 0.5;
+        exponent += 1;
+    }
+    if exponent > 127 {
+        return [255, 255, 255, 255];
+    }
+    let scale = mantissa * 256.0 / max_value;
+    [
+        (value[0].max(0.0) * scale) as u8,
+        (value[1].max(0.0) * scale) as u8,
+        (value[2].max(0.0) * scale) as u8,
+        (exponent + 128) as u8,
+    ]
+}
+
+// Run-length encodes one component of a scanline.
+fn write_rle_component<W: Write>(writer: &mut W, data: &[u8]) -> io::Result<()> {
+    let mut current = 0;
+    while current < data.len() {
+        // Look for the next run that is long enough to be encoded.
+        let mut run_start = current;
+        let mut run_length = 0;
+        while run_start < data.len() {
+            run_length = 1;
+            while run_start + run_length < data.len()
+                && run_length < MAX_RUN_LENGTH
+                && data[run_start + run_length] == data[run_start]
+            {
+                run_length += 1;
+            }
+            if run_length >= MIN_RUN_LENGTH {
+                break;
+            }
+            run_start += run_length;
+        }
+        if run_length < MIN_RUN_LENGTH {
+            run_start = data.len();
+        }
+
+        // Values before the run are written as literal dumps.
+        while current < run_start {
+            let count = (run_start - current).min(128);
+            writer.write_all(&[count as u8])?;
+            writer.write_all(&data[current..current + count])?;
+            current += count;
+        }
+
+        if run_start < data.len() {
+            writer.write_all(&[128 + run_length as u8, data[run_start]])?;
+            current = run_start + run_length;
+        }
+    }
+    Ok(())
+}
+
+/// Writes a Radiance RGBE image, run-length encoded when the width allows it.
+pub fn write_rgbe<W: Write>(writer: &mut W, samples: &[Vec<Point3<f32>>]) -> io::Result<()> {
+    let width = samples.len();
+    let height = samples[0].len();
+    write!(
+        writer,
+        "#?RADIANCE\nFORMAT=32-bit_rle_rgbe\n\n-Y {} +X {}\n",
+        height, width
+    )?;
+
+    let use_rle = (MIN_RLE_WIDTH..=MAX_RLE_WIDTH).contains(&width);
+    let mut components: Vec<Vec<u8>> = (0..4).map(|_| Vec::with_capacity(width)).collect();
+    for y in 0..height {
+        let scanline = samples.iter().map(|column| to_rgbe(&column[y]));
+        if !use_rle {
+            for pixel in scanline {
+                writer.write_all(&pixel)?;
+            }
+            continue;
+        }
+
+        for component in components.iter_mut() {
+            component.clear();
+        }
+        for pixel in scanline {
+            for (component, value) in components.iter_mut().zip(pixel.iter()) {
+                component.push(*value);
+            }
+        }
+        writer.write_all(&[2, 2, (width >> 8) as u8, (width & 0xff) as u8])?;
+        for component in &components {
+            write_rle_component(writer, component)?;
+        }
+    }
+    Ok(())
+}
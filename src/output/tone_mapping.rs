@@ -0,0 +1,112 @@
+use std::str::FromStr;
+
+use nalgebra::{Matrix3, Point3, Vector3};
+use serde::{Deserialize, Serialize};
+
+#[derive(Clone, Copy, Debug, PartialEq, Serialize, Deserialize)]
+pub enum ToneMapOperator {
+    Clamp,
+    Reinhard,
+    // John Hable's Uncharted 2 filmic curve.
+    Filmic,
+    // Stephen Hill's fit of the ACES reference and output transforms.
+    Aces,
+}
+
+impl FromStr for ToneMapOperator {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_lowercase().as_str() {
+            "clamp" => Ok(ToneMapOperator::Clamp),
+            "reinhard" => Ok(ToneMapOperator::Reinhard),
+            "filmic" | "hable" => Ok(ToneMapOperator::Filmic),
+            "aces" => Ok(ToneMapOperator::Aces),
+            _ => Err(format!("unknown tone mapping operator: {}", s)),
+        }
+    }
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize)]
+#[serde(default)]
+pub struct ToneMapping {
+    pub operator: ToneMapOperator,
+    // Exposure correction in stops, applied before the operator.
+    pub exposure: f32,
+}
+
+impl Default for ToneMapping {
+    fn default() -> Self {
+        ToneMapping {
+            operator: ToneMapOperator::Clamp,
+            exposure: 0.0,
+        }
+    }
+}
+
+fn luminance(color: &Vector3<f32>) -> f32 {
+    color.dot(&Vector3::new(0.2126, 0.7152, 0.0722))
+}
+
+fn hable_curve(x: f32) -> f32 {
+    let (a, b, c, d, e, f) = (0.15, 0.50, 0.10, 0.20, 0.02, 0.30);
+    ((x * (a * x + c * b) + d * e) / (x * (a * x + b) + d * f)) - e / f
+}
+
+fn hable(color: &Vector3<f32>) -> Vector3<f32> {
+    let exposure_bias = 2.0;
+    let white_point = 11.2;
+    let white_scale = 1.0 / hable_curve(white_point);
+    color.map(|c| hable_curve(exposure_bias * c) * white_scale)
+}
+
+fn aces_fitted(color: &Vector3<f32>) -> Vector3<f32> {
+    // sRGB => XYZ => D65_2_D60 => AP1 => RRT_SAT
+    let input_matrix = Matrix3::new(
+        0.59719, 0.35458, 0.04823, 0.07600, 0.90834, 0.01566, 0.02840, 0.13383, 0.83777,
+    );
+    // ODT_SAT => XYZ => D60_2_D65 => sRGB
+    let output_matrix = Matrix3::new(
+        1.60475, -0.53108, -0.07367, -0.10208, 1.10813, -0.00605, -0.00327, -0.07276, 1.07602,
+    );
+    let rrt_and_odt_fit = |v: f32| {
+        let a = v * (v + 0.024_578_6) - 0.000_090_537;
+        let b = v * (0.983_729 * v + 0.432_951) + 0.238_081;
+        a / b
+    };
+    output_matrix * (input_matrix * color).map(rrt_and_odt_fit)
+}
+
+/// sRGB opto-electronic transfer function.
+// Unlike `clamp`, `max` and `min` also map NaN values to 0.
+#[allow(clippy::manual_clamp)]
+pub fn linear_to_srgb(value: f32) -> f32 {
+    let value = value.max(0.0).min(1.0);
+    if value <= 0.003_130_8 {
+        12.92 * value
+    } else {
+        1.055 * value.powf(1.0 / 2.4) - 0.055
+    }
+}
+
+impl ToneMapping {
+    /// Maps a linear radiance value to display-referred linear values in [0, 1].
+    #[allow(clippy::manual_clamp)]
+    pub fn map(&self, value: &Point3<f32>) -> Vector3<f32> {
+        let color = 2.0f32.powf(self.exposure) * value.coords.map(|c| c.max(0.0));
+        let mapped = match self.operator {
+            ToneMapOperator::Clamp => color,
+            ToneMapOperator::Reinhard => color / (1.0 + luminance(&color)),
+            ToneMapOperator::Filmic => hable(&color),
+            ToneMapOperator::Aces => aces_fitted(&color),
+        };
+        mapped.map(|c| c.max(0.0).min(1.0))
+    }
+
+    /// Tone maps a linear radiance value and encodes it as 8-bit sRGB.
+    pub fn to_srgb8(&self, value: &Point3<f32>) -> [u8; 3] {
+        let mapped = self.map(value);
+        let encode = |c: f32| (255.0 * linear_to_srgb(c) + 0.5) as u8;
+        [encode(mapped[0]), encode(mapped[1]), encode(mapped[2])]
+    }
+}
@@ -0,0 +1,87 @@
+use nalgebra::Point2;
+
+use crate::samplers::{hash, permute, to_unit_float, Sampler};
+
+// Dimensions beyond this number of primes are drawn uniformly at random.
+const MAX_DIMENSIONS: usize = 256;
+
+/// Halton sequence, with one prime base per dimension. The digits of the points are Owen
+/// scrambled differently for each pixel and dimension.
+pub struct HaltonSampler {
+    seed: u64,
+    primes: Vec<u64>,
+    pixel_index: u64,
+    sample_index: u64,
+    dimension: usize,
+}
+
+impl HaltonSampler {
+    pub fn new(seed: u64) -> Self {
+        let mut primes = Vec::with_capacity(MAX_DIMENSIONS);
+        let mut candidate = 2;
+        while primes.len() < MAX_DIMENSIONS {
+            if primes.iter().all(|p| candidate % p != 0) {
+                primes.push(candidate);
+            }
+            candidate += 1;
+        }
+        HaltonSampler {
+            seed,
+            primes,
+            pixel_index: 0,
+            sample_index: 0,
+            dimension: 0,
+        }
+    }
+}
+
+impl Sampler for HaltonSampler {
+    fn start_sample(&mut self, pixel_index: u64, sample_index: u64) {
+        self.pixel_index = pixel_index;
+        self.sample_index = sample_index;
+        self.dimension = 0;
+    }
+
+    fn next_1d(&mut self) -> f32 {
+        let seed = hash(&[self.seed, self.pixel_index, self.dimension as u64]);
+        let value = match self.primes.get(self.dimension) {
+            Some(&base) => scrambled_radical_inverse(base, self.sample_index, seed),
+            None => to_unit_float(hash(&[seed, self.sample_index]) as u32),
+        };
+        self.dimension += 1;
+        value.min(1.0 - f32::EPSILON)
+    }
+
+    fn next_2d(&mut self) -> Point2<f32> {
+        let x = self.next_1d();
+        Point2::new(x, self.next_1d())
+    }
+}
+
+// Digits of `index` in `base` mirrored around the decimal point, each one permuted depending
+// on the digits before it. Digits are generated until the float precision is reached, since
+// the permutations also change the trailing zeros.
+fn scrambled_radical_inverse(base: u64, mut index: u64, seed: u64) -> f32 {
+    let inverse_base = 1.0 / base as f32;
+    let mut scale = 1.0;
+    let mut reversed = 0;
+    while 1.0 - scale < 1.0 {
+        let digit_seed = hash(&[seed, reversed]) as u32;
+        let digit = permute((index % base) as u32, base as u32, digit_seed);
+        reversed = reversed * base + u64::from(digit);
+        scale *= inverse_base;
+        index /= base;
+    }
+    reversed as f32 * scale
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::samplers::tests::check_sampler;
+
+    #[test]
+    fn values_are_reproducible_and_in_unit_interval() {
+        check_sampler(&mut HaltonSampler::new(7));
+    }
+}
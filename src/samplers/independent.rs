@@ -0,0 +1,47 @@
+use nalgebra::Point2;
+use rand::Rng;
+use rand_pcg::Pcg32;
+
+use crate::samplers::Sampler;
+use crate::sampling::pixel_sample_rng;
+
+/// Uniform random values, without any correlation between samples.
+pub struct IndependentSampler {
+    seed: u64,
+    rng: Pcg32,
+}
+
+impl IndependentSampler {
+    pub fn new(seed: u64) -> Self {
+        IndependentSampler {
+            seed,
+            rng: pixel_sample_rng(seed, 0, 0),
+        }
+    }
+}
+
+impl Sampler for IndependentSampler {
+    fn start_sample(&mut self, pixel_index: u64, sample_index: u64) {
+        self.rng = pixel_sample_rng(self.seed, pixel_index, sample_index);
+    }
+
+    fn next_1d(&mut self) -> f32 {
+        self.rng.gen_range(0.0, 1.0)
+    }
+
+    fn next_2d(&mut self) -> Point2<f32> {
+        let x = self.next_1d();
+        Point2::new(x, self.next_1d())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::samplers::tests::check_sampler;
+
+    #[test]
+    fn values_are_reproducible_and_in_unit_interval() {
+        check_sampler(&mut IndependentSampler::new(7));
+    }
+}
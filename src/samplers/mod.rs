@@ -0,0 +1,151 @@
+use nalgebra::Point2;
+use serde::{Deserialize, Serialize};
+
+use crate::sampling::mix_bits;
+
+/// Source of the sample values of a pixel sample, handed out one dimension after the other.
+///
+/// `start_sample` restarts the dimensions, so the values only depend on the seed, the pixel,
+/// the sample index and the order in which they are drawn.
+pub trait Sampler {
+    fn start_sample(&mut self, pixel_index: u64, sample_index: u64);
+    fn next_1d(&mut self) -> f32;
+    fn next_2d(&mut self) -> Point2<f32>;
+}
+
+mod halton;
+mod independent;
+mod sobol;
+mod stratified;
+
+pub use halton::HaltonSampler;
+pub use independent::IndependentSampler;
+pub use sobol::SobolSampler;
+pub use stratified::StratifiedSampler;
+
+#[derive(Clone, Copy, Default, Serialize, Deserialize)]
+pub enum SamplerData {
+    #[default]
+    Independent,
+    // Jittered strata, one per sample of the pixel.
+    Stratified,
+    // Halton sequence, randomly shifted for each pixel.
+    Halton,
+    // Owen scrambled Sobol sequence.
+    Sobol,
+}
+
+impl SamplerData {
+    pub fn to_sampler(self, seed: u64, samples_per_pixel: u32) -> Box<dyn Sampler> {
+        match self {
+            SamplerData::Independent => Box::new(IndependentSampler::new(seed)),
+            SamplerData::Stratified => Box::new(StratifiedSampler::new(seed, samples_per_pixel)),
+            SamplerData::Halton => Box::new(HaltonSampler::new(seed)),
+            SamplerData::Sobol => Box::new(SobolSampler::new(seed)),
+        }
+    }
+}
+
+// Hash of several values, used to seed the randomization of each pixel and dimension.
+fn hash(values: &[u64]) -> u64 {
+    values.iter().fold(0x853c_49e6_748f_ea9b, |hash, &value| {
+        mix_bits(hash ^ mix_bits(value))
+    })
+}
+
+// Maps 32 random bits to [0, 1), keeping only as many bits as a float can represent.
+fn to_unit_float(bits: u32) -> f32 {
+    (bits >> 8) as f32 * (1.0 / (1u32 << 24) as f32)
+}
+
+// Random permutation of [0, length) evaluated one element at a time, see Kensler,
+// "Correlated Multi-Jittered Sampling", 2013.
+fn permute(mut index: u32, length: u32, seed: u32) -> u32 {
+    let mut mask = length - 1;
+    mask |= mask >> 1;
+    mask |= mask >> 2;
+    mask |= mask >> 4;
+    mask |= mask >> 8;
+    mask |= mask >> 16;
+    loop {
+        index ^= seed;
+        index = index.wrapping_mul(0xe170_893d);
+        index ^= seed >> 16;
+        index ^= (index & mask) >> 4;
+        index ^= seed >> 8;
+        index = index.wrapping_mul(0x0929_eb3f);
+        index ^= seed >> 23;
+        index ^= (index & mask) >> 1;
+        index = index.wrapping_mul(1 | seed >> 27);
+        index = index.wrapping_mul(0x6935_fa69);
+        index ^= (index & mask) >> 11;
+        index = index.wrapping_mul(0x74dc_b303);
+        index ^= (index & mask) >> 2;
+        index = index.wrapping_mul(0x9e50_1cc3);
+        index ^= (index & mask) >> 2;
+        index = index.wrapping_mul(0xc860_a3df);
+        index &= mask;
+        index ^= index >> 5;
+        if index < length {
+            break;
+        }
+    }
+    (index.wrapping_add(seed)) % length
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // Values of the first dimensions of a few samples of a few pixels.
+    pub fn values(sampler: &mut dyn Sampler) -> Vec<f32> {
+        let mut values = Vec::new();
+        for pixel_index in 0..4 {
+            for sample_index in 0..64 {
+                sampler.start_sample(pixel_index, sample_index);
+                for _ in 0..4 {
+                    values.push(sampler.next_1d());
+                    let point = sampler.next_2d();
+                    values.extend_from_slice(&[point[0], point[1]]);
+                }
+            }
+        }
+        values
+    }
+
+    /// Checks that the values are in [0, 1) and only depend on the pixel and sample indices.
+    pub fn check_sampler(sampler: &mut dyn Sampler) {
+        let first_values = values(sampler);
+        assert!(first_values
+            .iter()
+            .all(|&value| value >= 0.0 && value < 1.0));
+
+        sampler.start_sample(3, 17);
+        let first_sample = (sampler.next_1d(), sampler.next_2d());
+        sampler.start_sample(0, 0);
+        sampler.next_2d();
+        sampler.start_sample(3, 17);
+        assert_eq!((sampler.next_1d(), sampler.next_2d()), first_sample);
+        assert_eq!(values(sampler), first_values);
+    }
+
+    #[test]
+    fn permute_is_a_bijection() {
+        for &length in &[1, 2, 3, 5, 7, 10, 17, 100, 1000] {
+            for seed in 0..16 {
+                let mut seen = vec![false; length as usize];
+                for index in 0..length {
+                    let permuted = permute(index, length, hash(&[seed]) as u32);
+                    assert!(!seen[permuted as usize]);
+                    seen[permuted as usize] = true;
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn to_unit_float_is_below_one() {
+        assert_eq!(to_unit_float(0), 0.0);
+        assert!(to_unit_float(u32::MAX) < 1.0);
+    }
+}
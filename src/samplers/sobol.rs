@@ -0,0 +1,88 @@
+use nalgebra::Point2;
+
+use crate::samplers::{hash, to_unit_float, Sampler};
+
+/// Owen scrambled Sobol points. Every pair of dimensions uses the first two dimensions of the
+/// Sobol sequence, with its own scrambling and shuffled indices to decorrelate the pairs, see
+/// Burley, "Practical Hash-based Owen Scrambling", 2020.
+pub struct SobolSampler {
+    seed: u64,
+    pixel_index: u64,
+    sample_index: u64,
+    dimension: u64,
+}
+
+impl SobolSampler {
+    pub fn new(seed: u64) -> Self {
+        SobolSampler {
+            seed,
+            pixel_index: 0,
+            sample_index: 0,
+            dimension: 0,
+        }
+    }
+
+    // Seed of the scrambling of the current dimension, the shuffled index of the sample.
+    fn next_dimension(&mut self) -> (u32, u32) {
+        let seed = hash(&[self.seed, self.pixel_index, self.dimension]);
+        self.dimension += 1;
+        let index = nested_uniform_scramble(self.sample_index as u32, seed as u32);
+        ((seed >> 32) as u32, index)
+    }
+}
+
+impl Sampler for SobolSampler {
+    fn start_sample(&mut self, pixel_index: u64, sample_index: u64) {
+        self.pixel_index = pixel_index;
+        self.sample_index = sample_index;
+        self.dimension = 0;
+    }
+
+    fn next_1d(&mut self) -> f32 {
+        let (seed, index) = self.next_dimension();
+        to_unit_float(nested_uniform_scramble(index.reverse_bits(), seed))
+    }
+
+    fn next_2d(&mut self) -> Point2<f32> {
+        let (seed, index) = self.next_dimension();
+        let x = nested_uniform_scramble(index.reverse_bits(), seed);
+        let y = nested_uniform_scramble(sobol_second_dimension(index), seed ^ 0x9e37_79b9);
+        Point2::new(to_unit_float(x), to_unit_float(y))
+    }
+}
+
+// Second dimension of the Sobol sequence, whose generator matrix is Pascal's triangle.
+fn sobol_second_dimension(mut index: u32) -> u32 {
+    let mut direction = 1 << 31;
+    let mut value = 0;
+    while index != 0 {
+        if index & 1 != 0 {
+            value ^= direction;
+        }
+        index >>= 1;
+        direction ^= direction >> 1;
+    }
+    value
+}
+
+// Owen scrambling of the bits of `value`, from the most significant one.
+fn nested_uniform_scramble(value: u32, seed: u32) -> u32 {
+    let mut x = value.reverse_bits();
+    x ^= x.wrapping_mul(0x3d20_adea);
+    x = x.wrapping_add(seed);
+    x = x.wrapping_mul((seed >> 16) | 1);
+    x ^= x.wrapping_mul(0x0552_6c56);
+    x ^= x.wrapping_mul(0x53a2_2864);
+    x.reverse_bits()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::samplers::tests::check_sampler;
+
+    #[test]
+    fn values_are_reproducible_and_in_unit_interval() {
+        check_sampler(&mut SobolSampler::new(7));
+    }
+}
@@ -0,0 +1,105 @@
+use nalgebra::Point2;
+
+use crate::samplers::{hash, permute, to_unit_float, Sampler};
+
+/// Jittered sampling: the samples of a pixel fall in distinct strata of each dimension, which
+/// are matched to the samples by a different random permutation for every dimension.
+pub struct StratifiedSampler {
+    seed: u64,
+    samples_per_pixel: u32,
+    pixel_index: u64,
+    sample_index: u64,
+    dimension: u64,
+}
+
+impl StratifiedSampler {
+    pub fn new(seed: u64, samples_per_pixel: u32) -> Self {
+        StratifiedSampler {
+            seed,
+            samples_per_pixel: samples_per_pixel.max(1),
+            pixel_index: 0,
+            sample_index: 0,
+            dimension: 0,
+        }
+    }
+
+    // Stratum among `count` of the current sample, and two jitter values within it. Samples
+    // beyond `count` start new rounds of strata with other permutations.
+    fn stratum(&mut self, count: u32) -> (u32, u32, u32) {
+        let round = self.sample_index / u64::from(count);
+        let index = (self.sample_index % u64::from(count)) as u32;
+        let permutation_seed = hash(&[self.seed, self.pixel_index, self.dimension, round]);
+        let jitter = hash(&[permutation_seed, self.sample_index]);
+        self.dimension += 1;
+        (
+            permute(index, count, permutation_seed as u32),
+            jitter as u32,
+            (jitter >> 32) as u32,
+        )
+    }
+}
+
+impl Sampler for StratifiedSampler {
+    fn start_sample(&mut self, pixel_index: u64, sample_index: u64) {
+        self.pixel_index = pixel_index;
+        self.sample_index = sample_index;
+        self.dimension = 0;
+    }
+
+    fn next_1d(&mut self) -> f32 {
+        let count = self.samples_per_pixel;
+        let (stratum, jitter, _) = self.stratum(count);
+        ((stratum as f32 + to_unit_float(jitter)) / count as f32).min(1.0 - f32::EPSILON)
+    }
+
+    // The strata form a grid that is as square as possible, with at least one cell per sample.
+    fn next_2d(&mut self) -> Point2<f32> {
+        let columns = (self.samples_per_pixel as f32).sqrt().ceil() as u32;
+        let rows = (self.samples_per_pixel + columns - 1) / columns;
+        let (cell, jitter_x, jitter_y) = self.stratum(columns * rows);
+        Point2::new(
+            (((cell % columns) as f32 + to_unit_float(jitter_x)) / columns as f32)
+                .min(1.0 - f32::EPSILON),
+            (((cell / columns) as f32 + to_unit_float(jitter_y)) / rows as f32)
+                .min(1.0 - f32::EPSILON),
+        )
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::samplers::tests::check_sampler;
+
+    #[test]
+    fn values_are_reproducible_and_in_unit_interval() {
+        for &samples_per_pixel in &[1, 5, 16] {
+            check_sampler(&mut StratifiedSampler::new(7, samples_per_pixel));
+        }
+    }
+
+    #[test]
+    fn samples_fall_in_distinct_strata() {
+        for &samples_per_pixel in &[1, 2, 6, 9, 10] {
+            let mut sampler = StratifiedSampler::new(7, samples_per_pixel);
+            let columns = (samples_per_pixel as f32).sqrt().ceil() as u32;
+            let rows = (samples_per_pixel + columns - 1) / columns;
+            for pixel_index in 0..8 {
+                let mut strata_1d = vec![false; samples_per_pixel as usize];
+                let mut strata_2d = vec![false; (columns * rows) as usize];
+                for sample_index in 0..u64::from(samples_per_pixel) {
+                    sampler.start_sample(pixel_index, sample_index);
+                    let stratum = (sampler.next_1d() * samples_per_pixel as f32) as usize;
+                    assert!(!strata_1d[stratum]);
+                    strata_1d[stratum] = true;
+
+                    let point = sampler.next_2d();
+                    let cell = (point[1] * rows as f32) as u32 * columns
+                        + (point[0] * columns as f32) as u32;
+                    assert!(!strata_2d[cell as usize]);
+                    strata_2d[cell as usize] = true;
+                }
+            }
+        }
+    }
+}
@@ -0,0 +1,60 @@
+use nalgebra::Point2;
+
+use std::f32::consts::{FRAC_PI_2, FRAC_PI_4, PI};
+
+/// Uniform sampling of a lens aperture of unit radius, either a disk or a regular polygon.
+pub struct ApertureSampler {
+    // Number of diaphragm blades, the aperture is a disk below 3.
+    blades: u32,
+    // Angle of the first polygon vertex, in radians.
+    rotation: f32,
+}
+
+impl ApertureSampler {
+    pub fn new(blades: u32, rotation: f32) -> Self {
+        ApertureSampler { blades, rotation }
+    }
+
+    pub fn sample(&self, samples: &Point2<f32>) -> Point2<f32> {
+        if self.blades < 3 {
+            sample_concentric_disk(samples)
+        } else {
+            self.sample_polygon(samples)
+        }
+    }
+
+    // The polygon is split in identical triangles between the center and each side, the first
+    // sample picks the triangle and is then reused inside of it.
+    fn sample_polygon(&self, samples: &Point2<f32>) -> Point2<f32> {
+        let blades = self.blades as f32;
+        let scaled = samples[0] * blades;
+        let side = scaled.floor().min(blades - 1.0);
+        let u = scaled - side;
+
+        let angle = |i: f32| self.rotation + 2.0 * PI * i / blades;
+        let (start, end) = (angle(side), angle(side + 1.0));
+        let sqrt_v = samples[1].sqrt();
+        // Barycentric coordinates of the two vertices on the side, the third one is the center.
+        let (a, b) = (sqrt_v * (1.0 - u), sqrt_v * u);
+        Point2::new(
+            a * start.cos() + b * end.cos(),
+            a * start.sin() + b * end.sin(),
+        )
+    }
+}
+
+// Shirley and Chiu's mapping of the unit square to the unit disk, which keeps the
+// stratification of the samples.
+fn sample_concentric_disk(samples: &Point2<f32>) -> Point2<f32> {
+    let x = 2.0 * samples[0] - 1.0;
+    let y = 2.0 * samples[1] - 1.0;
+    if x == 0.0 && y == 0.0 {
+        return Point2::origin();
+    }
+    let (radius, theta) = if x.abs() > y.abs() {
+        (x, FRAC_PI_4 * (y / x))
+    } else {
+        (y, FRAC_PI_2 - FRAC_PI_4 * (x / y))
+    };
+    Point2::new(radius * theta.cos(), radius * theta.sin())
+}
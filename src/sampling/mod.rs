@@ -3,3 +3,9 @@ pub use sphere_sampling::*;
 
 mod shape_sampling;
 pub use shape_sampling::*;
+
+mod aperture_sampling;
+pub use aperture_sampling::*;
+
+mod random;
+pub use random::*;
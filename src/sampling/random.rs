@@ -0,0 +1,17 @@
+use rand_pcg::Pcg32;
+
+// SplitMix64 finalizer, spreads nearby inputs over the whole 64-bit range.
+pub fn mix_bits(value: u64) -> u64 {
+    let mut z = value.wrapping_add(0x9e37_79b9_7f4a_7c15);
+    z = (z ^ (z >> 30)).wrapping_mul(0xbf58_476d_1ce4_e5b9);
+    z = (z ^ (z >> 27)).wrapping_mul(0x94d0_49bb_1331_11eb);
+    z ^ (z >> 31)
+}
+
+/// Random stream of one sample of one pixel.
+///
+/// The stream only depends on its arguments, so a render is reproducible
+/// whatever the number of threads or the order in which pixels are computed.
+pub fn pixel_sample_rng(seed: u64, pixel_index: u64, sample_index: u64) -> Pcg32 {
+    Pcg32::new(mix_bits(seed ^ mix_bits(pixel_index)), sample_index)
+}
@@ -1,44 +1,142 @@
-use nalgebra::{Point2, Point3, Vector2, Vector3};
+use nalgebra::{Point2, Point3, Vector3};
 use ncollide3d::{
     math::Isometry,
-    shape::{Ball, Cuboid, Shape, ShapeHandle},
+    shape::{Ball, Cuboid, ShapeHandle, TriMesh},
 };
 
-use std::f32::consts::{FRAC_1_PI, PI};
+use std::f32::consts::PI;
 
 use crate::math::angles_to_vector;
 use crate::sampling::UniformSphereSampler;
 
-pub struct UniformShapeSampler;
+/// Samples points uniformly over the surface of an emitter.
+///
+/// Only balls, cuboids and triangle meshes can be sampled, `new` returns `None` for any
+/// other shape.
+pub struct UniformShapeSampler {
+    shape: ShapeHandle<f32>,
+    // Triangle area distribution and total area, only used by triangle meshes.
+    triangle_cdf: Vec<f32>,
+    mesh_area: f32,
+}
 
 impl UniformShapeSampler {
+    pub fn new(shape: &ShapeHandle<f32>) -> Option<Self> {
+        UniformShapeSampler::with_faces(shape, |_| true)
+    }
+
+    /// Sampler of the faces of a triangle mesh for which `included` is true, the other shapes
+    /// are sampled whole.
+    pub fn with_faces(shape: &ShapeHandle<f32>, included: impl Fn(usize) -> bool) -> Option<Self> {
+        let (triangle_cdf, mesh_area) = if let Some(mesh) = shape.as_shape::<TriMesh<f32>>() {
+            let (cdf, area) = triangle_cdf(mesh, included);
+            if area > 0.0 {
+                (cdf, area)
+            } else {
+                return None;
+            }
+        } else if shape.is_shape::<Ball<f32>>() || shape.is_shape::<Cuboid<f32>>() {
+            (Vec::new(), 0.0)
+        } else {
+            return None;
+        };
+        Some(UniformShapeSampler {
+            shape: shape.clone(),
+            triangle_cdf,
+            mesh_area,
+        })
+    }
+
+    /// Returns a point on the surface, its normal and the probability density of the
+    /// point with respect to surface area.
     pub fn sample(
         &self,
-        shape: &ShapeHandle<f32>,
         position: &Isometry<f32>,
         samples: &Point2<f32>,
-    ) -> (Point3<f32>, f32) {
-        if shape.is_shape::<Ball<f32>>() {
-            let sampler = UniformBallSampler::new(shape.as_shape::<Ball<f32>>().unwrap());
-            let (pos, prob) = sampler.sample(samples);
-            return (position * pos, prob);
-        } else if shape.is_shape::<Cuboid<f32>>() {
-            let sampler = UniformCuboidSampler::new(shape.as_shape::<Cuboid<f32>>().unwrap());
-            let (pos, prob) = sampler.sample(samples);
-            return (position * pos, prob);
+    ) -> (Point3<f32>, Vector3<f32>, f32) {
+        let (point, normal, probability) = if let Some(ball) = self.shape.as_shape::<Ball<f32>>() {
+            UniformBallSampler::new(ball).sample(samples)
+        } else if let Some(cuboid) = self.shape.as_shape::<Cuboid<f32>>() {
+            UniformCuboidSampler::new(cuboid).sample(samples)
+        } else if let Some(mesh) = self.shape.as_shape::<TriMesh<f32>>() {
+            UniformTriMeshSampler::new(mesh, &self.triangle_cdf, self.mesh_area).sample(samples)
         } else {
-            return (Point3::new(0.0, 0.0, 0.0), 1.0f32);
+            unreachable!("UniformShapeSampler built for an unsupported shape")
+        };
+        (position * point, position * normal, probability)
+    }
+
+    /// Samples a point of the surface as seen from `reference`. Returns the point, its normal
+    /// and the probability density of the direction towards the point with respect to solid
+    /// angle at `reference`.
+    pub fn sample_from(
+        &self,
+        position: &Isometry<f32>,
+        reference: &Point3<f32>,
+        samples: &Point2<f32>,
+    ) -> (Point3<f32>, Vector3<f32>, f32) {
+        if let Some(ball) = self.shape.as_shape::<Ball<f32>>() {
+            let center = Point3::from(position.translation.vector);
+            let sampler = SolidAngleBallSampler::new(ball);
+            if let Some(sample) = sampler.sample(&center, reference, samples) {
+                return sample;
+            }
         }
+
+        let (point, normal, area_probability) = self.sample(position, samples);
+        (
+            point,
+            normal,
+            area_to_solid_angle(area_probability, reference, &point, &normal),
+        )
     }
-}
 
-struct OriginSampler;
-impl OriginSampler {
-    pub fn sample(&self, samples: &Point2<f32>) -> (Point3<f32>, f32) {
-        (Point3::new(0.0, 0.0, 0.0), 1.0f32)
+    /// Solid angle density with which `sample_from` returns `point` as seen from `reference`.
+    pub fn pdf_from(
+        &self,
+        position: &Isometry<f32>,
+        reference: &Point3<f32>,
+        point: &Point3<f32>,
+        normal: &Vector3<f32>,
+    ) -> f32 {
+        if let Some(ball) = self.shape.as_shape::<Ball<f32>>() {
+            let center = Point3::from(position.translation.vector);
+            if let Some(probability) = SolidAngleBallSampler::new(ball).pdf(&center, reference) {
+                return probability;
+            }
+        }
+        area_to_solid_angle(self.area_pdf(), reference, point, normal)
+    }
+
+    // Density of the points returned by `sample` with respect to surface area.
+    fn area_pdf(&self) -> f32 {
+        if let Some(ball) = self.shape.as_shape::<Ball<f32>>() {
+            let radius = ball.radius();
+            1.0 / (4.0 * PI * radius * radius)
+        } else if let Some(cuboid) = self.shape.as_shape::<Cuboid<f32>>() {
+            let half_sizes = cuboid.half_extents();
+            let half_area = 4.0
+                * (half_sizes[0] * half_sizes[1]
+                    + half_sizes[1] * half_sizes[2]
+                    + half_sizes[0] * half_sizes[2]);
+            1.0 / (2.0 * half_area)
+        } else {
+            1.0 / self.mesh_area
+        }
     }
 }
 
+fn area_to_solid_angle(
+    area_probability: f32,
+    reference: &Point3<f32>,
+    point: &Point3<f32>,
+    normal: &Vector3<f32>,
+) -> f32 {
+    let to_point = point - reference;
+    let cos_theta = normal.dot(&to_point).abs() / to_point.norm();
+    area_probability * to_point.norm_squared() / cos_theta
+}
+
 struct UniformBallSampler<'a> {
     ball: &'a Ball<f32>,
 }
@@ -48,10 +146,77 @@ impl<'a> UniformBallSampler<'a> {
         UniformBallSampler { ball }
     }
 
-    pub fn sample(&self, samples: &Point2<f32>) -> (Point3<f32>, f32) {
+    pub fn sample(&self, samples: &Point2<f32>) -> (Point3<f32>, Vector3<f32>, f32) {
         let sphere_sampler = UniformSphereSampler;
         let (vector, probability) = sphere_sampler.sample(samples);
-        (Point3::from(self.ball.radius() * vector), probability)
+        let radius = self.ball.radius();
+        (
+            Point3::from(radius * vector),
+            vector,
+            probability / (radius * radius),
+        )
+    }
+}
+
+// Samples the cone of directions under which a ball is seen, instead of its whole surface.
+struct SolidAngleBallSampler<'a> {
+    ball: &'a Ball<f32>,
+}
+
+impl<'a> SolidAngleBallSampler<'a> {
+    pub fn new(ball: &'a Ball<f32>) -> Self {
+        SolidAngleBallSampler { ball }
+    }
+
+    // Returns 1 - cos(theta_max) of the cone under which the ball is seen from `reference`,
+    // or `None` when `reference` is inside the ball.
+    fn cone(&self, center: &Point3<f32>, reference: &Point3<f32>) -> Option<f32> {
+        let radius = self.ball.radius();
+        let distance_2 = (center - reference).norm_squared();
+        if distance_2 <= radius * radius {
+            return None;
+        }
+        let sin_theta_max_2 = (radius * radius) / distance_2;
+        // Avoids cancellation in 1 - cos_theta_max for small or distant balls.
+        if sin_theta_max_2 < 1.0e-4 {
+            Some(0.5 * sin_theta_max_2)
+        } else {
+            Some(1.0 - (1.0 - sin_theta_max_2).sqrt())
+        }
+    }
+
+    pub fn pdf(&self, center: &Point3<f32>, reference: &Point3<f32>) -> Option<f32> {
+        self.cone(center, reference)
+            .map(|one_minus_cos_theta_max| 1.0 / (2.0 * PI * one_minus_cos_theta_max))
+    }
+
+    // Returns `None` when `reference` is inside the ball.
+    pub fn sample(
+        &self,
+        center: &Point3<f32>,
+        reference: &Point3<f32>,
+        samples: &Point2<f32>,
+    ) -> Option<(Point3<f32>, Vector3<f32>, f32)> {
+        let one_minus_cos_theta_max = self.cone(center, reference)?;
+        let radius = self.ball.radius();
+        let axis = center - reference;
+        let distance = axis.norm();
+        let axis = axis / distance;
+
+        let cos_theta = 1.0 - samples[0] * one_minus_cos_theta_max;
+        let sin_theta_2 = (1.0 - cos_theta * cos_theta).max(0.0);
+        let phi = 2.0 * PI * samples[1];
+        let direction = angles_to_vector(phi, cos_theta.min(1.0).acos(), &axis);
+
+        // First intersection of the sampled direction with the ball.
+        let toi = distance * cos_theta
+            - (radius * radius - distance * distance * sin_theta_2)
+                .max(0.0)
+                .sqrt();
+        let point = reference + toi * direction;
+        let normal = (point - center) / radius;
+
+        Some((point, normal, 1.0 / (2.0 * PI * one_minus_cos_theta_max)))
     }
 }
 
@@ -64,7 +229,7 @@ impl<'a> UniformCuboidSampler<'a> {
         UniformCuboidSampler { cuboid }
     }
 
-    pub fn sample(&self, samples: &Point2<f32>) -> (Point3<f32>, f32) {
+    pub fn sample(&self, samples: &Point2<f32>) -> (Point3<f32>, Vector3<f32>, f32) {
         let half_sizes = self.cuboid.half_extents();
         let face_area_x = 4.0 * half_sizes[1] * half_sizes[2];
         let face_area_y = 4.0 * half_sizes[0] * half_sizes[2];
@@ -120,7 +285,73 @@ impl<'a> UniformCuboidSampler<'a> {
             ),
             _ => Point3::new(0.0, 0.0, 0.0),
         };
+        let mut surface_normal = Vector3::new(0.0, 0.0, 0.0);
+        surface_normal[r_index] = face_sign;
+
+        (surface_point, surface_normal, 1.0f32 / (2.0 * total_area))
+    }
+}
+
+struct UniformTriMeshSampler<'a> {
+    mesh: &'a TriMesh<f32>,
+    cdf: &'a [f32],
+    area: f32,
+}
+
+impl<'a> UniformTriMeshSampler<'a> {
+    pub fn new(mesh: &'a TriMesh<f32>, cdf: &'a [f32], area: f32) -> Self {
+        UniformTriMeshSampler { mesh, cdf, area }
+    }
+
+    fn triangle(&self, index: usize) -> (Point3<f32>, Point3<f32>, Point3<f32>) {
+        triangle(self.mesh, index)
+    }
+
+    pub fn sample(&self, samples: &Point2<f32>) -> (Point3<f32>, Vector3<f32>, f32) {
+        // Pick a triangle with a probability proportional to its area, then rescale the
+        // sample to place the point inside of it. Triangles without area are never picked,
+        // as the previous value of the distribution is already above the sample.
+        let face_index = self
+            .cdf
+            .partition_point(|&value| value <= samples[0])
+            .min(self.cdf.len() - 1);
+        let face_start = if face_index > 0 {
+            self.cdf[face_index - 1]
+        } else {
+            0.0
+        };
+        let u = ((samples[0] - face_start) / (self.cdf[face_index] - face_start)).clamp(0.0, 1.0);
+
+        let (a, b, c) = self.triangle(face_index);
+        let su = u.sqrt();
+        let point = a + su * (1.0 - samples[1]) * (b - a) + su * samples[1] * (c - a);
+        let normal = (b - a).cross(&(c - a)).normalize();
+
+        (point, normal, 1.0 / self.area)
+    }
+}
+
+fn triangle(mesh: &TriMesh<f32>, index: usize) -> (Point3<f32>, Point3<f32>, Point3<f32>) {
+    let points = mesh.points();
+    let indices = mesh.faces()[index].indices;
+    (points[indices[0]], points[indices[1]], points[indices[2]])
+}
 
-        (surface_point, 1.0f32 / (2.0 * total_area))
+// Normalized cumulative distribution of the areas of the included triangles, the others count
+// as empty, along with the total area.
+fn triangle_cdf(mesh: &TriMesh<f32>, included: impl Fn(usize) -> bool) -> (Vec<f32>, f32) {
+    let mut area = 0.0f32;
+    let mut cdf = (0..mesh.faces().len())
+        .map(|index| {
+            if included(index) {
+                let (a, b, c) = triangle(mesh, index);
+                area += 0.5 * (b - a).cross(&(c - a)).norm();
+            }
+            area
+        })
+        .collect::<Vec<_>>();
+    for value in cdf.iter_mut() {
+        *value /= area;
     }
+    (cdf, area)
 }
@@ -1,23 +1,9 @@
-use nalgebra::{Point2, Vector2, Vector3};
+use nalgebra::{Point2, Vector3};
 
 use std::f32::consts::{FRAC_1_PI, PI};
 
 use crate::math::angles_to_vector;
 
-pub struct UniformSampler2 {
-    dims: Vector2<f32>,
-}
-
-impl UniformSampler2 {
-    pub fn new(dims: Vector2<f32>) -> UniformSampler2 {
-        UniformSampler2 { dims }
-    }
-
-    pub fn sample(&self, input: &Point2<f32>) -> Point2<f32> {
-        Point2::new(input[0] * self.dims[0], input[1] * self.dims[1])
-    }
-}
-
 pub struct UniformSphereSampler;
 
 impl UniformSphereSampler {
@@ -1,19 +1,43 @@
-use nalgebra::{Point3, Vector2, Vector3};
+use nalgebra::{Point2, Point3, Vector2, Vector3};
 use ncollide3d::{
     math::Isometry,
-    pipeline::object::{CollisionGroups, CollisionObjectSlabHandle, GeometricQueryType},
+    pipeline::{
+        object::{
+            CollisionGroups, CollisionObjectSlab, CollisionObjectSlabHandle, GeometricQueryType,
+        },
+        FirstInterferenceWithRay,
+    },
+    query::{Ray, RayCast},
+    shape::{Ball, FeatureId, TriMesh},
     world::CollisionWorld,
 };
+use rayon::{ThreadPool, ThreadPoolBuilder};
 use serde::{Deserialize, Serialize};
+use std::f32::consts::FRAC_1_PI;
 
-use crate::camera::{Camera, CameraBuilder};
-use crate::object::{ObjectData, WorldObjectData};
+use crate::camera::{Camera, CameraBuilder, CameraData};
+use crate::error::{Error, Result};
+use crate::film::Film;
+use crate::integrators::{Integrator, IntegratorData};
+use crate::object::{HitRecord, ObjectData, WorldObjectData};
+use crate::sampling::UniformShapeSampler;
+use crate::settings::RenderSettings;
+
+pub struct Emitter {
+    pub handle: CollisionObjectSlabHandle,
+    pub sampler: UniformShapeSampler,
+    pub emission: (f32, Vector3<f32>),
+    // Mesh material whose faces are sampled, the whole shape is sampled when it is `None`.
+    pub material: Option<usize>,
+}
 
 pub struct Scene {
     pub camera: Camera,
+    pub integrator: Box<dyn Integrator>,
+    pub settings: RenderSettings,
     pub collision_world: CollisionWorld<f32, WorldObjectData>,
 
-    pub emitters: Vec<CollisionObjectSlabHandle>,
+    pub emitters: Vec<Emitter>,
 }
 
 impl Scene {
@@ -28,6 +52,8 @@ impl Scene {
                 .screen_dimensions(Vector2::new(0.8, 0.6))
                 .resolution(Vector2::new(800, 600))
                 .build(),
+            integrator: IntegratorData::default().to_integrator(),
+            settings: RenderSettings::default(),
             collision_world: CollisionWorld::<f32, WorldObjectData>::new(0.0001f32),
             emitters: Vec::new(),
         }
@@ -36,28 +62,125 @@ impl Scene {
     pub fn set_camera(&mut self, camera: Camera) {
         self.camera = camera;
     }
-    pub fn add_object(&mut self, mut data: ObjectData) {
-        let shape = std::mem::take(&mut data.shape);
-        let position = std::mem::take(&mut data.position);
-        let world_data = data.to_world_data();
-
-        match (position, shape) {
-            (Some(pos), Some(s)) => {
-                let mut is_emitter = world_data.emission != None;
-
-                let (object_handle, _) = self.collision_world.add(
-                    pos,
-                    s.get_handle(),
-                    CollisionGroups::new(),
-                    GeometricQueryType::Contacts(0.0001, 0.0001),
-                    world_data,
-                );
-                if is_emitter {
-                    self.emitters.push(object_handle);
+
+    pub fn set_integrator(&mut self, integrator: Box<dyn Integrator>) {
+        self.integrator = integrator;
+    }
+
+    pub fn add_object(&mut self, mut data: ObjectData) -> Result<()> {
+        let shape =
+            std::mem::take(&mut data.shape).ok_or(Error::MissingField { field: "shape" })?;
+        let position =
+            std::mem::take(&mut data.position).ok_or(Error::MissingField { field: "position" })?;
+        let mut world_data = data.to_world_data()?;
+
+        let (shape_handle, shading) = shape.get_handle()?;
+        world_data.set_shading(shading)?;
+        // An emissive object is sampled whole, otherwise the faces of each emissive material
+        // of a mesh form a separate emitter.
+        let mut emitters = Vec::new();
+        if let Some(emission) = world_data.emission {
+            let sampler =
+                UniformShapeSampler::new(&shape_handle).ok_or_else(|| Error::UnsupportedShape {
+                    message: "emitters must be balls, cuboids or triangle meshes with a \
+                              non-zero area"
+                        .to_owned(),
+                })?;
+            emitters.push((sampler, emission, None));
+        } else {
+            for (index, material) in world_data.materials.iter().enumerate() {
+                let emission = match material.emission {
+                    Some(emission) => emission,
+                    None => continue,
+                };
+                let face_materials = &world_data.face_materials;
+                // Materials without any face, or only degenerate ones, cannot be sampled.
+                if let Some(sampler) = UniformShapeSampler::with_faces(&shape_handle, |face| {
+                    face_materials[face] == Some(index)
+                }) {
+                    emitters.push((sampler, emission, Some(index)));
                 }
             }
-            _ => (),
         }
+
+        let (object_handle, _) = self.collision_world.add(
+            position,
+            shape_handle,
+            CollisionGroups::new(),
+            GeometricQueryType::Contacts(0.0001, 0.0001),
+            world_data,
+        );
+        for (sampler, emission, material) in emitters {
+            self.emitters.push(Emitter {
+                handle: object_handle,
+                sampler,
+                emission,
+                material,
+            });
+        }
+        Ok(())
+    }
+
+    /// Emitter sampling the surface of the object `handle` at `hit`.
+    pub fn emitter(&self, handle: CollisionObjectSlabHandle, hit: &HitRecord) -> Option<&Emitter> {
+        let material = self
+            .collision_world
+            .collision_object(handle)?
+            .data()
+            .material_index(hit);
+        self.emitters.iter().find(|emitter| {
+            emitter.handle == handle && (emitter.material.is_none() || emitter.material == material)
+        })
+    }
+
+    /// Closest intersection of `ray` with the objects of the scene.
+    ///
+    /// Unlike `CollisionWorld::first_interference_with_ray`, the shapes are hollow so rays
+    /// starting inside an object hit its boundary, and the returned normal always points
+    /// outside of the object, whichever side it is hit from. Texture coordinates are given
+    /// for balls, cuboids and triangle meshes with texture coordinates.
+    pub fn intersect(
+        &self,
+        ray: &Ray<f32>,
+        max_toi: f32,
+    ) -> Option<FirstInterferenceWithRay<'_, f32, CollisionObjectSlab<f32, WorldObjectData>>> {
+        let objects = &self.collision_world.objects;
+        let narrow_phase = |handle: CollisionObjectSlabHandle, ray: &Ray<f32>, max_toi: f32| {
+            let co = objects.get(handle)?;
+            let shape = co.shape();
+            let mut inter =
+                shape.toi_and_normal_and_uv_with_ray(co.position(), ray, max_toi, false)?;
+
+            // The normals given by ncollide depend on the side the shape is hit from. Balls
+            // use the direction from their center, triangle meshes the side of the face hit,
+            // and other shapes are exited by the ray when its origin is inside of them.
+            if shape.is_shape::<Ball<f32>>() {
+                let center = Point3::from(co.position().translation.vector);
+                inter.normal = (ray.point_at(inter.toi) - center).normalize();
+                inter.uvs = Some(ball_uv(
+                    &co.position().inverse_transform_vector(&inter.normal),
+                ));
+            } else if let Some(mesh) = shape.as_shape::<TriMesh<f32>>() {
+                if let FeatureId::Face(i) = inter.feature {
+                    if i >= mesh.faces().len() {
+                        inter.normal = -inter.normal;
+                    }
+                }
+            } else if let Some(query) = shape.as_point_query() {
+                let exiting = query.contains_point(co.position(), &ray.origin);
+                if (inter.normal.dot(&ray.dir) > 0.0) != exiting {
+                    inter.normal = -inter.normal;
+                }
+            }
+            Some((handle, inter))
+        };
+
+        let (handle, inter) = self
+            .collision_world
+            .broad_phase
+            .first_interference_with_ray(ray, max_toi, &narrow_phase)?;
+        let co = objects.get(handle)?;
+        Some(FirstInterferenceWithRay { handle, co, inter })
     }
 
     pub fn perform_collision_phase(&mut self) {
@@ -65,14 +188,77 @@ impl Scene {
         self.collision_world.perform_narrow_phase();
     }
 
-    pub fn capture(&self, n_samples: u32) -> Vec<Vec<Point3<f32>>> {
-        self.camera.compute_samples(&self, n_samples)
+    fn thread_pool(&self) -> Result<ThreadPool> {
+        ThreadPoolBuilder::new()
+            .num_threads(self.settings.threads)
+            .build()
+            .map_err(|source| Error::ThreadPool { source })
+    }
+
+    pub fn capture(&self) -> Result<Vec<Vec<Point3<f32>>>> {
+        let film = self
+            .thread_pool()?
+            .install(|| self.camera.compute_samples(self, &self.settings))?;
+        Ok(film.pixels())
+    }
+
+    /// Renders the image in passes of one sample per pixel, calling `on_pass` with the number
+    /// of passes done and the film after each of them. Rendering stops once all the samples
+    /// are done or when `on_pass` returns false.
+    pub fn capture_progressive<F>(&self, on_pass: F) -> Result<Film>
+    where
+        F: FnMut(u32, &Film) -> bool,
+    {
+        self.resume_progressive(Film::new(self.camera.resolution()), 0, on_pass)
+    }
+
+    /// Continues a progressive capture from a film that already holds `samples_done` samples
+    /// per pixel, rendered with the same scene and settings.
+    pub fn resume_progressive<F>(
+        &self,
+        mut film: Film,
+        samples_done: u32,
+        mut on_pass: F,
+    ) -> Result<Film>
+    where
+        F: FnMut(u32, &Film) -> bool,
+    {
+        let pool = self.thread_pool()?;
+        let filter = self.settings.filter.to_filter()?;
+        for pass in samples_done..self.settings.samples_per_pixel {
+            pool.install(|| {
+                self.camera.render_samples(
+                    self,
+                    &self.settings,
+                    &*filter,
+                    pass..pass + 1,
+                    &mut film,
+                )
+            });
+            if !on_pass(pass + 1, &film) {
+                break;
+            }
+        }
+        Ok(film)
     }
 }
 
+// Spherical coordinates of a direction in the local frame of a ball, with the poles on the z
+// axis.
+fn ball_uv(normal: &Vector3<f32>) -> Point2<f32> {
+    Point2::new(
+        0.5 + normal[1].atan2(normal[0]) * 0.5 * FRAC_1_PI,
+        0.5 + normal[2].clamp(-1.0, 1.0).asin() * FRAC_1_PI,
+    )
+}
+
 #[derive(Default, Serialize, Deserialize)]
 pub struct SceneData {
-    pub camera: Option<Camera>,
+    pub camera: Option<CameraData>,
+    #[serde(default)]
+    pub integrator: Option<IntegratorData>,
+    #[serde(default)]
+    pub settings: RenderSettings,
     pub objects: Vec<ObjectData>,
 }
 
@@ -81,15 +267,23 @@ impl SceneData {
         self.objects.push(object);
     }
 
-    pub fn to_scene(self) -> Scene {
+    pub fn to_scene(self) -> Result<Scene> {
+        self.settings.check()?;
         let mut scene = Scene::new();
+        scene.settings = self.settings;
         match self.camera {
-            Some(camera) => scene.set_camera(camera),
+            Some(camera) => scene.set_camera(camera.to_camera()?),
+            None => (),
+        }
+        match self.integrator {
+            Some(integrator) => scene.set_integrator(integrator.to_integrator()),
             None => (),
         }
-        for object in self.objects {
-            scene.add_object(object);
+        for (index, object) in self.objects.into_iter().enumerate() {
+            scene
+                .add_object(object)
+                .map_err(|error| error.in_object(index))?;
         }
-        scene
+        Ok(scene)
     }
 }
@@ -0,0 +1,62 @@
+use serde::{Deserialize, Serialize};
+
+use crate::error::{Error, Result};
+use crate::film::FilterData;
+use crate::output::ToneMapping;
+use crate::samplers::SamplerData;
+
+#[derive(Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct RenderSettings {
+    pub samples_per_pixel: u32,
+    // Number of samples per pixel the strata of the stratified sampler are laid out for, set
+    // when a resumed render is given more samples than it was started for.
+    #[serde(skip)]
+    pub stratum_samples: Option<u32>,
+    // Number of rendering threads, 0 uses one thread per core.
+    pub threads: usize,
+    pub tile_size: usize,
+    pub seed: u64,
+    pub sampler: SamplerData,
+    // Reconstruction filter spreading each sample over the pixels around it.
+    pub filter: FilterData,
+    // Only applied to 8-bit outputs, HDR images keep the linear radiance.
+    pub tone_mapping: ToneMapping,
+}
+
+impl Default for RenderSettings {
+    fn default() -> Self {
+        RenderSettings {
+            samples_per_pixel: 10,
+            stratum_samples: None,
+            threads: 0,
+            tile_size: 32,
+            seed: 0,
+            sampler: SamplerData::default(),
+            filter: FilterData::default(),
+            tone_mapping: ToneMapping::default(),
+        }
+    }
+}
+
+impl RenderSettings {
+    /// Checks the values that would render an empty image.
+    pub fn check(&self) -> Result<()> {
+        if self.samples_per_pixel == 0 {
+            return Err(Error::InvalidSettings {
+                message: "samples_per_pixel must be at least 1".to_owned(),
+            });
+        }
+        if self.tile_size == 0 {
+            return Err(Error::InvalidSettings {
+                message: "tile_size must be at least 1".to_owned(),
+            });
+        }
+        Ok(())
+    }
+
+    /// Number of samples per pixel the sampler is created for.
+    pub fn sampler_samples(&self) -> u32 {
+        self.stratum_samples.unwrap_or(self.samples_per_pixel)
+    }
+}
@@ -0,0 +1,192 @@
+use nalgebra::{Point2, Vector3};
+use serde::{Deserialize, Serialize};
+
+use crate::object::HitRecord;
+use crate::shaders::microfacet::{GGX, MIN_ROUGHNESS};
+use crate::shaders::{upper_hemisphere, BxDF, BRDF, BSDF};
+
+/// Complex index of refraction of a metal, for the red, green and blue channels.
+#[derive(Clone, Copy, Debug, Serialize, Deserialize)]
+pub enum ConductorMaterial {
+    Gold,
+    Copper,
+    Aluminium,
+    Silver,
+    Custom { eta: Vector3<f32>, k: Vector3<f32> },
+}
+
+impl ConductorMaterial {
+    // Measured values, sampled at 650, 550 and 450 nm.
+    fn eta_k(self) -> (Vector3<f32>, Vector3<f32>) {
+        match self {
+            ConductorMaterial::Gold => (
+                Vector3::new(0.143, 0.374, 1.442),
+                Vector3::new(3.983, 2.385, 1.603),
+            ),
+            ConductorMaterial::Copper => (
+                Vector3::new(0.200, 0.924, 1.102),
+                Vector3::new(3.912, 2.452, 2.142),
+            ),
+            ConductorMaterial::Aluminium => (
+                Vector3::new(1.657, 0.880, 0.521),
+                Vector3::new(9.224, 6.270, 4.837),
+            ),
+            ConductorMaterial::Silver => (
+                Vector3::new(0.155, 0.117, 0.138),
+                Vector3::new(4.828, 3.122, 2.147),
+            ),
+            ConductorMaterial::Custom { eta, k } => (eta, k),
+        }
+    }
+}
+
+// Unpolarized Fresnel reflectance of a conductor, for a single channel.
+fn fresnel_conductor(cos_theta: f32, eta: f32, k: f32) -> f32 {
+    let cos_2 = cos_theta.min(1.0) * cos_theta.min(1.0);
+    let sin_2 = 1.0 - cos_2;
+    let eta_2 = eta * eta;
+    let k_2 = k * k;
+
+    let t0 = eta_2 - k_2 - sin_2;
+    let a2_plus_b2 = (t0 * t0 + 4.0 * eta_2 * k_2).sqrt();
+    let t1 = a2_plus_b2 + cos_2;
+    let a = (0.5 * (a2_plus_b2 + t0)).max(0.0).sqrt();
+    let t2 = 2.0 * cos_theta * a;
+    let rs = (t1 - t2) / (t1 + t2);
+
+    let t3 = cos_2 * a2_plus_b2 + sin_2 * sin_2;
+    let t4 = t2 * sin_2;
+    let rp = rs * (t3 - t4) / (t3 + t4);
+
+    0.5 * (rp + rs)
+}
+
+// Without a microfacet distribution, the surface is a perfectly smooth mirror.
+#[derive(Clone, Debug)]
+pub struct ConductorBRDF {
+    distribution: Option<GGX>,
+    eta: Vector3<f32>,
+    k: Vector3<f32>,
+}
+
+impl ConductorBRDF {
+    pub fn new(material: ConductorMaterial, roughness: f32) -> Self {
+        let (eta, k) = material.eta_k();
+        ConductorBRDF {
+            distribution: if roughness >= MIN_ROUGHNESS {
+                Some(GGX::new(roughness))
+            } else {
+                None
+            },
+            eta,
+            k,
+        }
+    }
+
+    fn fresnel(&self, cos_theta: f32) -> Vector3<f32> {
+        Vector3::new(
+            fresnel_conductor(cos_theta, self.eta[0], self.k[0]),
+            fresnel_conductor(cos_theta, self.eta[1], self.k[1]),
+            fresnel_conductor(cos_theta, self.eta[2], self.k[2]),
+        )
+    }
+}
+
+impl BxDF for ConductorBRDF {
+    fn eval(&self, _: &HitRecord, dir1: &Vector3<f32>, dir2: &Vector3<f32>) -> Vector3<f32> {
+        let distribution = match &self.distribution {
+            Some(distribution) => distribution,
+            None => return Vector3::new(0.0, 0.0, 0.0),
+        };
+        if dir1[2] * dir2[2] <= 0.0 {
+            return Vector3::new(0.0, 0.0, 0.0);
+        }
+        let dir1 = upper_hemisphere(dir1, dir2);
+        let dir2 = upper_hemisphere(dir2, dir2);
+        let half = (dir1 + dir2).normalize();
+        self.fresnel(dir1.dot(&half)) * distribution.d(&half) * distribution.g(&dir1, &dir2)
+            / (4.0 * dir1[2] * dir2[2])
+    }
+
+    fn sample(
+        &self,
+        hit: &HitRecord,
+        dir: &Vector3<f32>,
+        samples: &Point2<f32>,
+    ) -> (Vector3<f32>, Vector3<f32>, f32) {
+        let distribution = match &self.distribution {
+            Some(distribution) => distribution,
+            None => {
+                // Dirac reflection, its value already includes the cosine.
+                let new_dir = Vector3::new(dir[0], dir[1], -dir[2]);
+                return (new_dir, self.fresnel(dir[2].abs()), 1.0);
+            }
+        };
+        let out_dir = -dir;
+        let upper_out_dir = upper_hemisphere(&out_dir, &out_dir);
+        let half = distribution.sample_visible_normal(&upper_out_dir, samples);
+        let upper_new_dir = 2.0 * upper_out_dir.dot(&half) * half - upper_out_dir;
+        let new_dir = upper_hemisphere(&upper_new_dir, &out_dir);
+        if upper_new_dir[2] <= 0.0 {
+            return (new_dir, Vector3::new(0.0, 0.0, 0.0), 0.0);
+        }
+        let brdf_value = self.eval(hit, &new_dir, &out_dir);
+        (new_dir, brdf_value, self.pdf(hit, dir, &new_dir))
+    }
+
+    fn pdf(&self, _: &HitRecord, dir: &Vector3<f32>, new_dir: &Vector3<f32>) -> f32 {
+        let distribution = match &self.distribution {
+            Some(distribution) => distribution,
+            None => return 0.0,
+        };
+        let out_dir = -dir;
+        if out_dir[2] * new_dir[2] <= 0.0 {
+            return 0.0;
+        }
+        let new_dir = upper_hemisphere(new_dir, &out_dir);
+        let out_dir = upper_hemisphere(&out_dir, &out_dir);
+        let half = (out_dir + new_dir).normalize();
+        // Jacobian of the reflection, from half vectors to directions.
+        distribution.visible_normal_pdf(&out_dir, &half) / (4.0 * out_dir.dot(&half))
+    }
+}
+
+impl BRDF for ConductorBRDF {}
+
+// ------------------------------------------------------------
+
+#[derive(Clone, Debug)]
+pub struct ConductorBSDF {
+    brdf: ConductorBRDF,
+}
+
+impl ConductorBSDF {
+    pub fn new(material: ConductorMaterial, roughness: f32) -> Self {
+        ConductorBSDF {
+            brdf: ConductorBRDF::new(material, roughness),
+        }
+    }
+}
+
+impl BSDF for ConductorBSDF {
+    fn eval(&self, hit: &HitRecord, dir1: &Vector3<f32>, dir2: &Vector3<f32>) -> Vector3<f32> {
+        self.brdf.eval(hit, dir1, dir2)
+    }
+
+    fn sample(
+        &self,
+        hit: &HitRecord,
+        dir: &Vector3<f32>,
+        samples: &Point2<f32>,
+    ) -> (Vector3<f32>, Vector3<f32>, f32) {
+        self.brdf.sample(hit, dir, samples)
+    }
+
+    fn pdf(&self, hit: &HitRecord, dir: &Vector3<f32>, new_dir: &Vector3<f32>) -> f32 {
+        self.brdf.pdf(hit, dir, new_dir)
+    }
+
+    fn is_diffuse(&self) -> bool {
+        self.brdf.distribution.is_some()
+    }
+}
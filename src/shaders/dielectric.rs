@@ -0,0 +1,285 @@
+use nalgebra::{Point2, Vector3};
+
+use crate::object::HitRecord;
+use crate::shaders::microfacet::{GGX, MIN_ROUGHNESS};
+use crate::shaders::{upper_hemisphere, BxDF, BSDF, BTDF};
+
+// Unpolarized Fresnel reflectance of a dielectric interface, with `cos_theta` measured on the
+// incident side and `eta` the ratio of the indices of refraction of the other side and of the
+// incident side.
+fn fresnel_dielectric(cos_theta: f32, eta: f32) -> f32 {
+    let sin_2_transmitted = (1.0 - cos_theta * cos_theta).max(0.0) / (eta * eta);
+    if sin_2_transmitted >= 1.0 {
+        // Total internal reflection
+        return 1.0;
+    }
+    let cos_transmitted = (1.0 - sin_2_transmitted).sqrt();
+    let rs = (cos_theta - eta * cos_transmitted) / (cos_theta + eta * cos_transmitted);
+    let rp = (eta * cos_theta - cos_transmitted) / (eta * cos_theta + cos_transmitted);
+    0.5 * (rs * rs + rp * rp)
+}
+
+fn reflect(dir: &Vector3<f32>, normal: &Vector3<f32>) -> Vector3<f32> {
+    2.0 * dir.dot(normal) * normal - dir
+}
+
+// Refracts `dir`, which leaves the interface on the side of `normal`, or returns `None` in case
+// of total internal reflection.
+fn refract(dir: &Vector3<f32>, normal: &Vector3<f32>, eta: f32) -> Option<Vector3<f32>> {
+    let cos_theta = dir.dot(normal);
+    let sin_2_transmitted = (1.0 - cos_theta * cos_theta).max(0.0) / (eta * eta);
+    if sin_2_transmitted >= 1.0 {
+        return None;
+    }
+    let cos_transmitted = (1.0 - sin_2_transmitted).sqrt();
+    Some(-dir / eta + (cos_theta / eta - cos_transmitted) * normal)
+}
+
+// Interface between the outside and a material of index of refraction `ior`, where the local
+// z axis points outside. Without a microfacet distribution, the interface is perfectly smooth.
+#[derive(Clone, Debug)]
+pub struct DielectricBTDF {
+    ior: f32,
+    distribution: Option<GGX>,
+}
+
+impl DielectricBTDF {
+    pub fn new(ior: f32, roughness: f32) -> Self {
+        DielectricBTDF {
+            ior,
+            distribution: if roughness >= MIN_ROUGHNESS {
+                Some(GGX::new(roughness))
+            } else {
+                None
+            },
+        }
+    }
+
+    // Relative index of refraction of the interface, seen from `dir`.
+    fn eta(&self, dir: &Vector3<f32>) -> f32 {
+        if dir[2] >= 0.0 {
+            self.ior
+        } else {
+            1.0 / self.ior
+        }
+    }
+
+    // Probability to sample a reflection rather than a refraction on a rough interface, seen
+    // from `dir` in the upper hemisphere. It never reaches 0 or 1 since microfacets can both
+    // reflect and refract whatever the macro surface Fresnel reflectance.
+    fn reflection_probability(dir: &Vector3<f32>, eta: f32) -> f32 {
+        fresnel_dielectric(dir[2], eta).clamp(0.05, 0.95)
+    }
+
+    fn sample_smooth(
+        &self,
+        out_dir: &Vector3<f32>,
+        samples: &Point2<f32>,
+    ) -> (Vector3<f32>, Vector3<f32>, f32) {
+        let eta = self.eta(out_dir);
+        let upper_out_dir = upper_hemisphere(out_dir, out_dir);
+        let normal = Vector3::new(0.0, 0.0, 1.0);
+        let reflectance = fresnel_dielectric(upper_out_dir[2], eta);
+
+        match refract(&upper_out_dir, &normal, eta) {
+            Some(upper_new_dir) if samples[0] >= reflectance => {
+                let new_dir = upper_hemisphere(&upper_new_dir, out_dir);
+                // Radiance is compressed into a smaller solid angle when entering a denser
+                // medium.
+                let transmittance = (1.0 - reflectance) / (eta * eta);
+                let value = Vector3::new(transmittance, transmittance, transmittance);
+                (new_dir, value, 1.0 - reflectance)
+            }
+            _ => {
+                let new_dir = Vector3::new(-out_dir[0], -out_dir[1], out_dir[2]);
+                let value = Vector3::new(reflectance, reflectance, reflectance);
+                (new_dir, value, reflectance)
+            }
+        }
+    }
+
+    fn eval_rough(&self, distribution: &GGX, dir1: &Vector3<f32>, dir2: &Vector3<f32>) -> f32 {
+        // Radiance flows from `dir1` towards `dir2`.
+        let eta = self.eta(dir2);
+        let in_dir = upper_hemisphere(dir1, dir2);
+        let out_dir = upper_hemisphere(dir2, dir2);
+        if in_dir[2] == 0.0 || out_dir[2] == 0.0 {
+            return 0.0;
+        }
+
+        if in_dir[2] > 0.0 {
+            let half = (in_dir + out_dir).normalize();
+            let reflectance = fresnel_dielectric(out_dir.dot(&half), eta);
+            reflectance * distribution.d(&half) * distribution.g(&in_dir, &out_dir)
+                / (4.0 * in_dir[2] * out_dir[2])
+        } else {
+            let half = match transmission_half_vector(&in_dir, &out_dir, eta) {
+                Some(half) => half,
+                None => return 0.0,
+            };
+            let in_cos = in_dir.dot(&half);
+            let out_cos = out_dir.dot(&half);
+            let reflectance = fresnel_dielectric(out_cos, eta);
+            let denominator = in_cos + out_cos / eta;
+            (1.0 - reflectance)
+                * distribution.d(&half)
+                * distribution.g(&in_dir, &out_dir)
+                * (in_cos * out_cos).abs()
+                / (denominator * denominator * in_dir[2].abs() * out_dir[2] * eta * eta)
+        }
+    }
+
+    fn pdf_rough(&self, distribution: &GGX, out_dir: &Vector3<f32>, new_dir: &Vector3<f32>) -> f32 {
+        let eta = self.eta(out_dir);
+        let new_dir = upper_hemisphere(new_dir, out_dir);
+        let out_dir = upper_hemisphere(out_dir, out_dir);
+        if new_dir[2] == 0.0 || out_dir[2] == 0.0 {
+            return 0.0;
+        }
+        let reflection_probability = Self::reflection_probability(&out_dir, eta);
+
+        if new_dir[2] > 0.0 {
+            let half = (new_dir + out_dir).normalize();
+            // Jacobian of the reflection, from half vectors to directions.
+            reflection_probability * distribution.visible_normal_pdf(&out_dir, &half)
+                / (4.0 * out_dir.dot(&half))
+        } else {
+            let half = match transmission_half_vector(&new_dir, &out_dir, eta) {
+                Some(half) => half,
+                None => return 0.0,
+            };
+            // Jacobian of the refraction, from half vectors to directions.
+            let denominator = new_dir.dot(&half) + out_dir.dot(&half) / eta;
+            (1.0 - reflection_probability)
+                * distribution.visible_normal_pdf(&out_dir, &half)
+                * new_dir.dot(&half).abs()
+                / (denominator * denominator)
+        }
+    }
+
+    fn sample_rough(
+        &self,
+        distribution: &GGX,
+        out_dir: &Vector3<f32>,
+        samples: &Point2<f32>,
+    ) -> (Vector3<f32>, Vector3<f32>, f32) {
+        let eta = self.eta(out_dir);
+        let upper_out_dir = upper_hemisphere(out_dir, out_dir);
+        let reflection_probability = Self::reflection_probability(&upper_out_dir, eta);
+
+        // Choose between reflection and refraction, and reuse the sample for the microfacet.
+        let (reflection, sample) = if samples[0] < reflection_probability {
+            (true, samples[0] / reflection_probability)
+        } else {
+            (
+                false,
+                (samples[0] - reflection_probability) / (1.0 - reflection_probability),
+            )
+        };
+        let half =
+            distribution.sample_visible_normal(&upper_out_dir, &Point2::new(sample, samples[1]));
+
+        let upper_new_dir = if reflection {
+            reflect(&upper_out_dir, &half)
+        } else {
+            match refract(&upper_out_dir, &half, eta) {
+                Some(new_dir) => new_dir,
+                None => return (-out_dir, Vector3::new(0.0, 0.0, 0.0), 0.0),
+            }
+        };
+        let new_dir = upper_hemisphere(&upper_new_dir, out_dir);
+        if (upper_new_dir[2] > 0.0) != reflection {
+            return (new_dir, Vector3::new(0.0, 0.0, 0.0), 0.0);
+        }
+
+        let value = self.eval_rough(distribution, &new_dir, out_dir);
+        let probability = self.pdf_rough(distribution, out_dir, &new_dir);
+        (new_dir, Vector3::new(value, value, value), probability)
+    }
+}
+
+// Microfacet normal refracting `in_dir` into `out_dir`, both given with `out_dir` in the upper
+// hemisphere, or `None` if no microfacet facing both directions does.
+fn transmission_half_vector(
+    in_dir: &Vector3<f32>,
+    out_dir: &Vector3<f32>,
+    eta: f32,
+) -> Option<Vector3<f32>> {
+    let mut half = (out_dir + eta * in_dir).try_normalize(1.0e-12)?;
+    if half[2] < 0.0 {
+        half = -half;
+    }
+    if out_dir.dot(&half) <= 0.0 || in_dir.dot(&half) >= 0.0 {
+        return None;
+    }
+    Some(half)
+}
+
+impl BxDF for DielectricBTDF {
+    fn eval(&self, _: &HitRecord, dir1: &Vector3<f32>, dir2: &Vector3<f32>) -> Vector3<f32> {
+        let value = match &self.distribution {
+            Some(distribution) => self.eval_rough(distribution, dir1, dir2),
+            None => 0.0,
+        };
+        Vector3::new(value, value, value)
+    }
+
+    fn sample(
+        &self,
+        _: &HitRecord,
+        dir: &Vector3<f32>,
+        samples: &Point2<f32>,
+    ) -> (Vector3<f32>, Vector3<f32>, f32) {
+        match &self.distribution {
+            Some(distribution) => self.sample_rough(distribution, &-dir, samples),
+            None => self.sample_smooth(&-dir, samples),
+        }
+    }
+
+    fn pdf(&self, _: &HitRecord, dir: &Vector3<f32>, new_dir: &Vector3<f32>) -> f32 {
+        match &self.distribution {
+            Some(distribution) => self.pdf_rough(distribution, &-dir, new_dir),
+            None => 0.0,
+        }
+    }
+}
+
+impl BTDF for DielectricBTDF {}
+
+// ------------------------------------------------------------
+
+#[derive(Clone, Debug)]
+pub struct DielectricBSDF {
+    btdf: DielectricBTDF,
+}
+
+impl DielectricBSDF {
+    pub fn new(ior: f32, roughness: f32) -> Self {
+        DielectricBSDF {
+            btdf: DielectricBTDF::new(ior, roughness),
+        }
+    }
+}
+
+impl BSDF for DielectricBSDF {
+    fn eval(&self, hit: &HitRecord, dir1: &Vector3<f32>, dir2: &Vector3<f32>) -> Vector3<f32> {
+        self.btdf.eval(hit, dir1, dir2)
+    }
+
+    fn sample(
+        &self,
+        hit: &HitRecord,
+        dir: &Vector3<f32>,
+        samples: &Point2<f32>,
+    ) -> (Vector3<f32>, Vector3<f32>, f32) {
+        self.btdf.sample(hit, dir, samples)
+    }
+
+    fn pdf(&self, hit: &HitRecord, dir: &Vector3<f32>, new_dir: &Vector3<f32>) -> f32 {
+        self.btdf.pdf(hit, dir, new_dir)
+    }
+
+    fn is_diffuse(&self) -> bool {
+        self.btdf.distribution.is_some()
+    }
+}
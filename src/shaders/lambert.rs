@@ -1,48 +1,64 @@
 use nalgebra::{Point2, Vector3};
 use std::f32::consts::FRAC_1_PI;
 
+use crate::object::HitRecord;
 use crate::sampling::CosineWeightedHemisphereSampler;
-use crate::shaders::{BxDF, BRDF, BSDF};
+use crate::shaders::{upper_hemisphere, BxDF, BRDF, BSDF};
+use crate::textures::Texture;
 
-#[derive(Clone, Debug)]
 pub struct LambertBRDF {
-    albedo: Vector3<f32>,
+    albedo: Box<dyn Texture>,
 }
 
 impl LambertBRDF {
-    pub fn new(albedo: Vector3<f32>) -> Self {
+    pub fn new(albedo: Box<dyn Texture>) -> Self {
         LambertBRDF { albedo }
     }
 }
 
 impl BxDF for LambertBRDF {
-    fn eval(&self, v1: &Vector3<f32>, v2: &Vector3<f32>) -> Vector3<f32> {
-        self.albedo * FRAC_1_PI
+    fn eval(&self, hit: &HitRecord, v1: &Vector3<f32>, v2: &Vector3<f32>) -> Vector3<f32> {
+        if v1[2] * v2[2] > 0.0 {
+            self.albedo.value(hit) * FRAC_1_PI
+        } else {
+            Vector3::new(0.0, 0.0, 0.0)
+        }
     }
 
     fn sample(
         &self,
+        hit: &HitRecord,
         dir: &Vector3<f32>,
         samples: &Point2<f32>,
     ) -> (Vector3<f32>, Vector3<f32>, f32) {
         let sampler = CosineWeightedHemisphereSampler;
         let (new_vector, probability) = sampler.sample(samples, &Vector3::new(0.0, 0.0, 1.0));
-        let brdf_value = self.eval(dir, &new_vector);
+        // Reflect on the side the surface is seen from.
+        let new_vector = upper_hemisphere(&new_vector, &-dir);
+        let brdf_value = self.eval(hit, &-dir, &new_vector);
         (new_vector, brdf_value, probability)
     }
+
+    fn pdf(&self, _: &HitRecord, dir: &Vector3<f32>, new_dir: &Vector3<f32>) -> f32 {
+        // `dir` points towards the surface, reflected directions leave on the other side.
+        if dir[2] * new_dir[2] < 0.0 {
+            new_dir[2].abs() * FRAC_1_PI
+        } else {
+            0.0
+        }
+    }
 }
 
 impl BRDF for LambertBRDF {}
 
 // ------------------------------------------------------------
 
-#[derive(Clone, Debug)]
 pub struct LambertBSDF {
     brdf: LambertBRDF,
 }
 
 impl LambertBSDF {
-    pub fn new(albedo: Vector3<f32>) -> Self {
+    pub fn new(albedo: Box<dyn Texture>) -> Self {
         LambertBSDF {
             brdf: LambertBRDF::new(albedo),
         }
@@ -50,15 +66,20 @@ impl LambertBSDF {
 }
 
 impl BSDF for LambertBSDF {
-    fn eval(&self, dir1: &Vector3<f32>, dir2: &Vector3<f32>) -> Vector3<f32> {
-        self.brdf.eval(dir1, dir2)
+    fn eval(&self, hit: &HitRecord, dir1: &Vector3<f32>, dir2: &Vector3<f32>) -> Vector3<f32> {
+        self.brdf.eval(hit, dir1, dir2)
     }
 
     fn sample(
         &self,
+        hit: &HitRecord,
         dir: &Vector3<f32>,
         samples: &Point2<f32>,
     ) -> (Vector3<f32>, Vector3<f32>, f32) {
-        self.brdf.sample(dir, samples)
+        self.brdf.sample(hit, dir, samples)
+    }
+
+    fn pdf(&self, hit: &HitRecord, dir: &Vector3<f32>, new_dir: &Vector3<f32>) -> f32 {
+        self.brdf.pdf(hit, dir, new_dir)
     }
 }
@@ -0,0 +1,90 @@
+use nalgebra::{Point2, Vector3};
+use std::f32::consts::PI;
+
+// Smallest roughness of the distribution, smoother surfaces are better treated as perfectly
+// smooth.
+pub const MIN_ROUGHNESS: f32 = 1.0e-3;
+
+// Isotropic GGX (Trowbridge-Reitz) distribution of microfacet normals, in the local frame
+// where the macro surface normal is the z axis.
+#[derive(Clone, Copy, Debug)]
+pub struct GGX {
+    alpha: f32,
+}
+
+impl GGX {
+    pub fn new(roughness: f32) -> Self {
+        // Perfectly smooth surfaces make the distribution a Dirac, keep it finite.
+        GGX {
+            alpha: roughness.max(MIN_ROUGHNESS),
+        }
+    }
+
+    // Density of microfacet normals, projected areas sum up to 1 over the hemisphere.
+    pub fn d(&self, half: &Vector3<f32>) -> f32 {
+        let cos_2 = half[2] * half[2];
+        if cos_2 <= 0.0 {
+            return 0.0;
+        }
+        let alpha_2 = self.alpha * self.alpha;
+        let denominator = cos_2 * (alpha_2 - 1.0) + 1.0;
+        alpha_2 / (PI * denominator * denominator)
+    }
+
+    fn lambda(&self, dir: &Vector3<f32>) -> f32 {
+        let cos_2 = dir[2] * dir[2];
+        let tan_2 = (1.0 - cos_2).max(0.0) / cos_2;
+        0.5 * ((1.0 + self.alpha * self.alpha * tan_2).sqrt() - 1.0)
+    }
+
+    // Smith masking function.
+    pub fn g1(&self, dir: &Vector3<f32>) -> f32 {
+        1.0 / (1.0 + self.lambda(dir))
+    }
+
+    // Height-correlated Smith masking-shadowing function.
+    pub fn g(&self, dir1: &Vector3<f32>, dir2: &Vector3<f32>) -> f32 {
+        1.0 / (1.0 + self.lambda(dir1) + self.lambda(dir2))
+    }
+
+    // Samples a microfacet normal visible from `dir`, which must be in the upper hemisphere.
+    // See Heitz, "Sampling the GGX Distribution of Visible Normals", 2018.
+    pub fn sample_visible_normal(&self, dir: &Vector3<f32>, samples: &Point2<f32>) -> Vector3<f32> {
+        // Stretch the view vector to work with the hemisphere configuration.
+        let stretched_dir =
+            Vector3::new(self.alpha * dir[0], self.alpha * dir[1], dir[2]).normalize();
+
+        let length_2 = stretched_dir[0] * stretched_dir[0] + stretched_dir[1] * stretched_dir[1];
+        let t1 = if length_2 > 0.0 {
+            Vector3::new(-stretched_dir[1], stretched_dir[0], 0.0) / length_2.sqrt()
+        } else {
+            Vector3::new(1.0, 0.0, 0.0)
+        };
+        let t2 = stretched_dir.cross(&t1);
+
+        // Sample the projected area of the hemisphere, as seen from the view vector.
+        let r = samples[0].sqrt();
+        let phi = 2.0 * PI * samples[1];
+        let p1 = r * phi.cos();
+        let s = 0.5 * (1.0 + stretched_dir[2]);
+        let p2 = (1.0 - s) * (1.0 - p1 * p1).max(0.0).sqrt() + s * r * phi.sin();
+        let p3 = (1.0 - p1 * p1 - p2 * p2).max(0.0).sqrt();
+        let stretched_normal = p1 * t1 + p2 * t2 + p3 * stretched_dir;
+
+        // Unstretch back to the ellipsoid configuration.
+        Vector3::new(
+            self.alpha * stretched_normal[0],
+            self.alpha * stretched_normal[1],
+            stretched_normal[2].max(0.0),
+        )
+        .normalize()
+    }
+
+    // Density of `sample_visible_normal(dir, _)` returning `half`.
+    pub fn visible_normal_pdf(&self, dir: &Vector3<f32>, half: &Vector3<f32>) -> f32 {
+        if dir[2] <= 0.0 {
+            return 0.0;
+        }
+        self.g1(dir) * dir.dot(half).max(0.0) * self.d(half) / dir[2]
+    }
+}
@@ -1,5 +1,6 @@
 use nalgebra::{Point2, Vector3};
 
+use crate::object::HitRecord;
 use crate::shaders::{BxDF, BRDF, BSDF};
 
 #[derive(Clone, Debug)]
@@ -12,12 +13,13 @@ impl MirrorBRDF {
 }
 
 impl BxDF for MirrorBRDF {
-    fn eval(&self, _: &Vector3<f32>, _: &Vector3<f32>) -> Vector3<f32> {
+    fn eval(&self, _: &HitRecord, _: &Vector3<f32>, _: &Vector3<f32>) -> Vector3<f32> {
         Vector3::new(0.0, 0.0, 0.0)
     }
 
     fn sample(
         &self,
+        _: &HitRecord,
         dir: &Vector3<f32>,
         samples: &Point2<f32>,
     ) -> (Vector3<f32>, Vector3<f32>, f32) {
@@ -26,6 +28,10 @@ impl BxDF for MirrorBRDF {
         let probability = 1.0;
         (new_dir, brdf_value, probability)
     }
+
+    fn pdf(&self, _: &HitRecord, _: &Vector3<f32>, _: &Vector3<f32>) -> f32 {
+        0.0
+    }
 }
 
 impl BRDF for MirrorBRDF {}
@@ -46,16 +52,21 @@ impl MirrorBSDF {
 }
 
 impl BSDF for MirrorBSDF {
-    fn eval(&self, dir1: &Vector3<f32>, dir2: &Vector3<f32>) -> Vector3<f32> {
-        self.brdf.eval(dir1, dir2)
+    fn eval(&self, hit: &HitRecord, dir1: &Vector3<f32>, dir2: &Vector3<f32>) -> Vector3<f32> {
+        self.brdf.eval(hit, dir1, dir2)
     }
 
     fn sample(
         &self,
+        hit: &HitRecord,
         dir: &Vector3<f32>,
         samples: &Point2<f32>,
     ) -> (Vector3<f32>, Vector3<f32>, f32) {
-        self.brdf.sample(dir, samples)
+        self.brdf.sample(hit, dir, samples)
+    }
+
+    fn pdf(&self, hit: &HitRecord, dir: &Vector3<f32>, new_dir: &Vector3<f32>) -> f32 {
+        self.brdf.pdf(hit, dir, new_dir)
     }
 
     fn is_diffuse(&self) -> bool {
@@ -1,14 +1,22 @@
 use nalgebra::{Point2, Vector3};
 use serde::{Deserialize, Serialize};
 
+use crate::error::Result;
+use crate::object::HitRecord;
+use crate::textures::ColorData;
+
 pub trait BxDF {
-    fn eval(&self, dir1: &Vector3<f32>, dir2: &Vector3<f32>) -> Vector3<f32>;
+    fn eval(&self, hit: &HitRecord, dir1: &Vector3<f32>, dir2: &Vector3<f32>) -> Vector3<f32>;
 
     fn sample(
         &self,
+        hit: &HitRecord,
         dir: &Vector3<f32>,
         samples: &Point2<f32>,
     ) -> (Vector3<f32>, Vector3<f32>, f32);
+
+    // Solid angle density with which `sample(dir, _)` returns `new_dir`.
+    fn pdf(&self, hit: &HitRecord, dir: &Vector3<f32>, new_dir: &Vector3<f32>) -> f32;
 }
 
 pub trait BRDF: BxDF {}
@@ -16,33 +24,70 @@ pub trait BRDF: BxDF {}
 pub trait BTDF: BxDF {}
 
 pub trait BSDF: Send + Sync {
-    fn eval(&self, dir1: &Vector3<f32>, dir2: &Vector3<f32>) -> Vector3<f32>;
+    fn eval(&self, hit: &HitRecord, dir1: &Vector3<f32>, dir2: &Vector3<f32>) -> Vector3<f32>;
 
     fn sample(
         &self,
+        hit: &HitRecord,
         dir: &Vector3<f32>,
         samples: &Point2<f32>,
     ) -> (Vector3<f32>, Vector3<f32>, f32);
 
+    // Solid angle density with which `sample(dir, _)` returns `new_dir`, always 0 for
+    // BSDFs that are not diffuse.
+    fn pdf(&self, hit: &HitRecord, dir: &Vector3<f32>, new_dir: &Vector3<f32>) -> f32;
+
     fn is_diffuse(&self) -> bool {
         true
     }
 }
 
+// Mirrors `dir` through the tangent plane when `side` is below it, so that two-sided BSDFs can
+// be evaluated as if they were seen from above.
+fn upper_hemisphere(dir: &Vector3<f32>, side: &Vector3<f32>) -> Vector3<f32> {
+    if side[2] < 0.0 {
+        Vector3::new(dir[0], dir[1], -dir[2])
+    } else {
+        *dir
+    }
+}
+
+pub mod conductor;
+pub mod dielectric;
 pub mod lambert;
+pub mod microfacet;
 pub mod mirror;
 
+pub use conductor::ConductorMaterial;
+
 #[derive(Serialize, Deserialize)]
 pub enum Shader {
-    Lambert(Vector3<f32>),
+    Lambert(ColorData),
     Mirror,
+    Conductor {
+        material: ConductorMaterial,
+        #[serde(default)]
+        roughness: f32,
+    },
+    Dielectric {
+        ior: f32,
+        #[serde(default)]
+        roughness: f32,
+    },
 }
 
 impl Shader {
-    pub fn to_bsdf(self) -> Box<dyn BSDF> {
-        match self {
-            Shader::Lambert(albedo) => Box::new(lambert::LambertBSDF::new(albedo)),
+    pub fn to_bsdf(self) -> Result<Box<dyn BSDF>> {
+        Ok(match self {
+            Shader::Lambert(albedo) => Box::new(lambert::LambertBSDF::new(albedo.to_texture()?)),
             Shader::Mirror => Box::new(mirror::MirrorBSDF::new()),
-        }
+            Shader::Conductor {
+                material,
+                roughness,
+            } => Box::new(conductor::ConductorBSDF::new(material, roughness)),
+            Shader::Dielectric { ior, roughness } => {
+                Box::new(dielectric::DielectricBSDF::new(ior, roughness))
+            }
+        })
     }
 }
@@ -0,0 +1,28 @@
+use nalgebra::Vector3;
+
+use crate::object::HitRecord;
+use crate::textures::Texture;
+
+pub struct CheckerboardTexture {
+    even: Box<dyn Texture>,
+    odd: Box<dyn Texture>,
+    scale: f32,
+}
+
+impl CheckerboardTexture {
+    pub fn new(even: Box<dyn Texture>, odd: Box<dyn Texture>, scale: f32) -> Self {
+        CheckerboardTexture { even, odd, scale }
+    }
+}
+
+impl Texture for CheckerboardTexture {
+    fn value(&self, hit: &HitRecord) -> Vector3<f32> {
+        let u = (hit.uv[0] * self.scale).floor() as i64;
+        let v = (hit.uv[1] * self.scale).floor() as i64;
+        if (u + v).rem_euclid(2) == 0 {
+            self.even.value(hit)
+        } else {
+            self.odd.value(hit)
+        }
+    }
+}
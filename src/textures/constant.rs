@@ -0,0 +1,20 @@
+use nalgebra::Vector3;
+
+use crate::object::HitRecord;
+use crate::textures::Texture;
+
+pub struct ConstantTexture {
+    color: Vector3<f32>,
+}
+
+impl ConstantTexture {
+    pub fn new(color: Vector3<f32>) -> Self {
+        ConstantTexture { color }
+    }
+}
+
+impl Texture for ConstantTexture {
+    fn value(&self, _: &HitRecord) -> Vector3<f32> {
+        self.color
+    }
+}
@@ -0,0 +1,429 @@
+use std::io::{self, Read};
+
+use nalgebra::Vector3;
+
+use crate::output::exr::{f16_to_f32, MAGIC_NUMBER};
+
+const TILED_FLAG: u8 = 0x02;
+const DEEP_FLAG: u8 = 0x08;
+const MULTIPART_FLAG: u8 = 0x10;
+
+fn invalid_data(message: &str) -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidData, message.to_owned())
+}
+
+#[derive(Clone, Copy)]
+enum Compression {
+    None,
+    Rle,
+    // Zlib compression of a single scanline, or of blocks of 16 scanlines.
+    Zips,
+    Zip,
+}
+
+impl Compression {
+    fn new(id: u8) -> io::Result<Self> {
+        let name = match id {
+            0 => return Ok(Compression::None),
+            1 => return Ok(Compression::Rle),
+            2 => return Ok(Compression::Zips),
+            3 => return Ok(Compression::Zip),
+            4 => "PIZ",
+            5 => "PXR24",
+            6 => "B44",
+            7 => "B44A",
+            8 => "DWAA",
+            9 => "DWAB",
+            _ => "unknown",
+        };
+        Err(invalid_data(&format!(
+            "unsupported OpenEXR compression {}, only uncompressed, RLE, ZIPS and ZIP files \
+             can be read",
+            name
+        )))
+    }
+
+    fn lines_per_chunk(self) -> usize {
+        match self {
+            Compression::Zip => 16,
+            _ => 1,
+        }
+    }
+
+    // Upper bound of the ratio between the decompressed and compressed sizes.
+    fn max_ratio(self) -> usize {
+        match self {
+            Compression::None => 1,
+            Compression::Rle => 64,
+            Compression::Zips | Compression::Zip => 1032,
+        }
+    }
+
+    fn decompress(self, data: &[u8], size: usize) -> io::Result<Vec<u8>> {
+        // Chunks that would not shrink are stored uncompressed.
+        if data.len() == size {
+            return Ok(data.to_vec());
+        }
+        let interleaved = match self {
+            Compression::None => return Err(invalid_data("invalid OpenEXR scanline size")),
+            Compression::Rle => rle_decode(data, size)?,
+            Compression::Zips | Compression::Zip => {
+                inflate::inflate_bytes_zlib(data).map_err(|message| {
+                    invalid_data(&format!("invalid OpenEXR ZIP data: {}", message))
+                })?
+            }
+        };
+        if interleaved.len() != size {
+            return Err(invalid_data("invalid OpenEXR compressed data size"));
+        }
+        Ok(reorder(predict(interleaved)))
+    }
+}
+
+// Runs of a repeated byte are stored as a count and the byte, other bytes as a negative count
+// followed by the bytes.
+fn rle_decode(data: &[u8], size: usize) -> io::Result<Vec<u8>> {
+    let mut decoded = Vec::with_capacity(size);
+    let mut cursor = Cursor { data, position: 0 };
+    while cursor.position < data.len() && decoded.len() <= size {
+        let count = cursor.bytes(1)?[0] as i8;
+        if count < 0 {
+            decoded.extend_from_slice(cursor.bytes(-i32::from(count) as usize)?);
+        } else {
+            let value = cursor.bytes(1)?[0];
+            decoded.extend(std::iter::repeat(value).take(count as usize + 1));
+        }
+    }
+    Ok(decoded)
+}
+
+// Compressed data stores the differences between consecutive bytes.
+fn predict(mut data: Vec<u8>) -> Vec<u8> {
+    for i in 1..data.len() {
+        data[i] = data[i - 1].wrapping_add(data[i]).wrapping_sub(128);
+    }
+    data
+}
+
+// Compressed data stores the even bytes first and then the odd ones.
+fn reorder(data: Vec<u8>) -> Vec<u8> {
+    let (even, odd) = data.split_at((data.len() + 1) / 2);
+    let mut ordered = Vec::with_capacity(data.len());
+    for (i, &byte) in even.iter().enumerate() {
+        ordered.push(byte);
+        if let Some(&byte) = odd.get(i) {
+            ordered.push(byte);
+        }
+    }
+    ordered
+}
+
+struct Cursor<'a> {
+    data: &'a [u8],
+    position: usize,
+}
+
+impl<'a> Cursor<'a> {
+    fn bytes(&mut self, count: usize) -> io::Result<&'a [u8]> {
+        let bytes = self
+            .position
+            .checked_add(count)
+            .and_then(|end| self.data.get(self.position..end))
+            .ok_or_else(|| invalid_data("truncated OpenEXR file"))?;
+        self.position += count;
+        Ok(bytes)
+    }
+
+    fn string(&mut self) -> io::Result<&'a str> {
+        let length = self
+            .data
+            .get(self.position..)
+            .and_then(|rest| rest.iter().position(|&b| b == 0))
+            .ok_or_else(|| invalid_data("truncated OpenEXR file"))?;
+        let bytes = self.bytes(length + 1)?;
+        std::str::from_utf8(&bytes[..length]).map_err(|_| invalid_data("invalid OpenEXR name"))
+    }
+
+    fn i32(&mut self) -> io::Result<i32> {
+        let mut bytes = [0; 4];
+        bytes.copy_from_slice(self.bytes(4)?);
+        Ok(i32::from_le_bytes(bytes))
+    }
+
+    // Sizes and offsets are stored as signed integers, negative values are invalid.
+    fn size(&mut self) -> io::Result<usize> {
+        let value = self.i32()?;
+        if value < 0 {
+            return Err(invalid_data("negative size in OpenEXR file"));
+        }
+        Ok(value as usize)
+    }
+
+    fn u64(&mut self) -> io::Result<u64> {
+        let mut bytes = [0; 8];
+        bytes.copy_from_slice(self.bytes(8)?);
+        Ok(u64::from_le_bytes(bytes))
+    }
+}
+
+struct Channel {
+    name: String,
+    pixel_type: i32,
+}
+
+impl Channel {
+    fn size(&self) -> usize {
+        match self.pixel_type {
+            1 => 2,
+            _ => 4,
+        }
+    }
+
+    fn value(&self, bytes: &[u8]) -> f32 {
+        match self.pixel_type {
+            0 => u32::from_le_bytes([bytes[0], bytes[1], bytes[2], bytes[3]]) as f32,
+            1 => f16_to_f32(u16::from_le_bytes([bytes[0], bytes[1]])),
+            _ => f32::from_le_bytes([bytes[0], bytes[1], bytes[2], bytes[3]]),
+        }
+    }
+}
+
+/// Reads a scanline OpenEXR image, either uncompressed or compressed with RLE or ZIP,
+/// returning its width, its height and its linear RGB pixels stored row by row from the top.
+/// Luminance only images are read as gray.
+pub fn read_exr<R: Read>(reader: &mut R) -> io::Result<(usize, usize, Vec<Vector3<f32>>)> {
+    let mut data = Vec::new();
+    reader.read_to_end(&mut data)?;
+    let mut cursor = Cursor {
+        data: &data,
+        position: 0,
+    };
+
+    if cursor.bytes(4)? != MAGIC_NUMBER {
+        return Err(invalid_data("not an OpenEXR file"));
+    }
+    let version = cursor.bytes(4)?;
+    if version[1] & (TILED_FLAG | DEEP_FLAG | MULTIPART_FLAG) != 0 {
+        return Err(invalid_data(
+            "only single-part scanline OpenEXR files are supported",
+        ));
+    }
+
+    let mut channels = Vec::new();
+    let mut compression = None;
+    let mut data_window = None;
+    loop {
+        let name = cursor.string()?;
+        if name.is_empty() {
+            break;
+        }
+        let _attribute_type = cursor.string()?;
+        let size = cursor.size()?;
+        let mut value = Cursor {
+            data: cursor.bytes(size)?,
+            position: 0,
+        };
+        match name {
+            "channels" => loop {
+                let channel_name = value.string()?;
+                if channel_name.is_empty() {
+                    break;
+                }
+                let pixel_type = value.i32()?;
+                // pLinear, reserved bytes and sampling.
+                value.bytes(12)?;
+                channels.push(Channel {
+                    name: channel_name.to_owned(),
+                    pixel_type,
+                });
+            },
+            "compression" => compression = Some(Compression::new(value.bytes(1)?[0])?),
+            "dataWindow" => {
+                data_window = Some([value.i32()?, value.i32()?, value.i32()?, value.i32()?]);
+            }
+            _ => {}
+        }
+    }
+
+    let compression = compression.ok_or_else(|| invalid_data("missing OpenEXR compression"))?;
+    let [x_min, y_min, x_max, y_max] =
+        data_window.ok_or_else(|| invalid_data("missing OpenEXR data window"))?;
+    let width = i64::from(x_max) - i64::from(x_min) + 1;
+    let height = i64::from(y_max) - i64::from(y_min) + 1;
+    if width <= 0 || height <= 0 {
+        return Err(invalid_data("empty OpenEXR data window"));
+    }
+    let (width, height) = (width as usize, height as usize);
+
+    // Index of the channel read for each of the R, G and B components.
+    let find = |name: &str| channels.iter().position(|c| c.name == name);
+    let components = match (find("R"), find("G"), find("B"), find("Y")) {
+        (Some(r), Some(g), Some(b), _) => [r, g, b],
+        (_, _, _, Some(y)) => [y, y, y],
+        _ => return Err(invalid_data("OpenEXR file without RGB or Y channels")),
+    };
+    // Channels are stored one after the other in each scanline.
+    let mut channel_offsets = Vec::new();
+    let mut line_size = 0;
+    for channel in &channels {
+        channel_offsets.push(line_size);
+        line_size += channel.size() * width;
+    }
+    // The size of the file bounds the size of the image, checked before allocating it.
+    let lines_per_chunk = compression.lines_per_chunk();
+    let n_chunks = (height + lines_per_chunk - 1) / lines_per_chunk;
+    if n_chunks.saturating_mul(8) > data.len()
+        || line_size.saturating_mul(height) > data.len().saturating_mul(compression.max_ratio())
+    {
+        return Err(invalid_data("truncated OpenEXR file"));
+    }
+
+    let mut chunk_offsets = Vec::with_capacity(n_chunks);
+    for _ in 0..n_chunks {
+        chunk_offsets.push(cursor.u64()? as usize);
+    }
+
+    let mut pixels = vec![Vector3::new(0.0, 0.0, 0.0); width * height];
+    for offset in chunk_offsets {
+        let mut chunk = Cursor {
+            data: &data,
+            position: offset,
+        };
+        let first_line = i64::from(chunk.i32()?) - i64::from(y_min);
+        if first_line < 0 || first_line >= height as i64 {
+            return Err(invalid_data("invalid OpenEXR scanline"));
+        }
+        let first_line = first_line as usize;
+        let n_lines = lines_per_chunk.min(height - first_line);
+        let size = chunk.size()?;
+        let lines = compression.decompress(chunk.bytes(size)?, line_size * n_lines)?;
+        for (y, line) in (first_line..).zip(lines.chunks(line_size)) {
+            for x in 0..width {
+                for (c, &channel_index) in components.iter().enumerate() {
+                    let channel = &channels[channel_index];
+                    let start = channel_offsets[channel_index] + x * channel.size();
+                    pixels[y * width + x][c] = channel.value(&line[start..]);
+                }
+            }
+        }
+    }
+    Ok((width, height, pixels))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::output::{write_exr, ExrPixelType};
+    use nalgebra::Point3;
+
+    // Image indexed by column and then row, as written by the renderer.
+    fn image(width: usize, height: usize) -> Vec<Vec<Point3<f32>>> {
+        (0..width)
+            .map(|x| {
+                (0..height)
+                    .map(|y| Point3::new(x as f32, y as f32, 0.25 * (x + y) as f32))
+                    .collect()
+            })
+            .collect()
+    }
+
+    fn exr_file(samples: &[Vec<Point3<f32>>], pixel_type: ExrPixelType) -> Vec<u8> {
+        let mut data = Vec::new();
+        write_exr(&mut data, samples, pixel_type).unwrap();
+        data
+    }
+
+    fn read_error(data: &[u8]) -> io::ErrorKind {
+        read_exr(&mut &data[..]).err().unwrap().kind()
+    }
+
+    // Inverse of `rle_decode`, `predict` and `reorder`.
+    fn rle_encode(line: &[u8]) -> Vec<u8> {
+        let mut interleaved = line.iter().step_by(2).cloned().collect::<Vec<_>>();
+        interleaved.extend(line.iter().skip(1).step_by(2));
+        let mut differences = vec![interleaved[0]];
+        for i in 1..interleaved.len() {
+            differences.push(
+                interleaved[i]
+                    .wrapping_sub(interleaved[i - 1])
+                    .wrapping_add(128),
+            );
+        }
+        let mut encoded = Vec::new();
+        let mut start = 0;
+        while start < differences.len() {
+            let mut end = start + 1;
+            while end < differences.len()
+                && end - start < 128
+                && differences[end] == differences[start]
+            {
+                end += 1;
+            }
+            if end - start > 1 {
+                encoded.extend_from_slice(&[(end - start - 1) as u8, differences[start]]);
+            } else {
+                encoded.extend_from_slice(&[-1i8 as u8, differences[start]]);
+            }
+            start = end;
+        }
+        encoded
+    }
+
+    #[test]
+    fn reads_written_images() {
+        let samples = image(5, 3);
+        let (width, height, pixels) =
+            read_exr(&mut &exr_file(&samples, ExrPixelType::Float)[..]).unwrap();
+        assert_eq!((width, height), (5, 3));
+        for x in 0..width {
+            for y in 0..height {
+                assert_eq!(pixels[y * width + x], samples[x][y].coords);
+            }
+        }
+    }
+
+    #[test]
+    fn reads_rle_compressed_chunks() {
+        // Half floats of a uniform image, whose even and odd bytes form long runs.
+        let samples = vec![vec![Point3::new(0.5, 0.25, 1.0)]; 16];
+        let data = exr_file(&samples, ExrPixelType::Half);
+        let line_size = 3 * 16 * 2;
+        // The file ends with the offset of the only chunk, its scanline, its size and its data.
+        let header_size = data.len() - 8 - 8 - line_size;
+        let mut compressed = data[..header_size].to_vec();
+        let attribute = b"compression\0compression\0\x01\0\0\0";
+        let position = compressed
+            .windows(attribute.len())
+            .position(|window| window == attribute)
+            .unwrap();
+        compressed[position + attribute.len()] = 1;
+
+        let encoded = rle_encode(&data[data.len() - line_size..]);
+        assert!(encoded.len() < line_size);
+        compressed.extend_from_slice(&(header_size as u64 + 8).to_le_bytes());
+        compressed.extend_from_slice(&0i32.to_le_bytes());
+        compressed.extend_from_slice(&(encoded.len() as i32).to_le_bytes());
+        compressed.extend_from_slice(&encoded);
+
+        let (width, height, pixels) = read_exr(&mut &compressed[..]).unwrap();
+        assert_eq!((width, height), (16, 1));
+        assert!(pixels.iter().all(|p| *p == Vector3::new(0.5, 0.25, 1.0)));
+    }
+
+    #[test]
+    fn rejects_truncated_files() {
+        let data = exr_file(&image(4, 4), ExrPixelType::Float);
+        for &size in &[2, 40, data.len() / 2, data.len() - 1] {
+            assert_eq!(read_error(&data[..size]), io::ErrorKind::InvalidData);
+        }
+    }
+
+    #[test]
+    fn rejects_negative_sizes() {
+        let mut data = exr_file(&image(4, 1), ExrPixelType::Float);
+        let line_size = 3 * 4 * 4;
+        let size_position = data.len() - line_size - 4;
+        data[size_position..size_position + 4].copy_from_slice(&(-1i32).to_le_bytes());
+        assert_eq!(read_error(&data), io::ErrorKind::InvalidData);
+    }
+}
@@ -0,0 +1,120 @@
+use std::fs::File;
+use std::io::{self, BufReader};
+use std::path::Path;
+
+use image::ImageError;
+use nalgebra::{Vector2, Vector3};
+use serde::{Deserialize, Serialize};
+
+use crate::object::HitRecord;
+use crate::textures::exr::read_exr;
+use crate::textures::Texture;
+
+/// Behavior of the texture coordinates outside of [0, 1].
+#[derive(Clone, Copy, Debug, Default, Serialize, Deserialize)]
+pub enum WrapMode {
+    #[default]
+    Repeat,
+    Clamp,
+    Mirror,
+}
+
+impl WrapMode {
+    fn wrap(self, index: i64, size: usize) -> usize {
+        let size = size as i64;
+        let index = match self {
+            WrapMode::Repeat => index.rem_euclid(size),
+            WrapMode::Clamp => index.max(0).min(size - 1),
+            WrapMode::Mirror => {
+                let index = index.rem_euclid(2 * size);
+                if index < size {
+                    index
+                } else {
+                    2 * size - 1 - index
+                }
+            }
+        };
+        index as usize
+    }
+}
+
+fn srgb_to_linear(value: f32) -> f32 {
+    if value <= 0.040_45 {
+        value / 12.92
+    } else {
+        ((value + 0.055) / 1.055).powf(2.4)
+    }
+}
+
+pub struct ImageTexture {
+    width: usize,
+    height: usize,
+    // Linear RGB values, row by row from the top of the image.
+    pixels: Vec<Vector3<f32>>,
+    wrap: WrapMode,
+    scale: Vector2<f32>,
+}
+
+impl ImageTexture {
+    pub fn load(path: &str, wrap: WrapMode, scale: Vector2<f32>) -> io::Result<Self> {
+        let is_exr = matches!(
+            Path::new(path).extension().and_then(|e| e.to_str()),
+            Some(e) if e.eq_ignore_ascii_case("exr")
+        );
+
+        let (width, height, pixels) = if is_exr {
+            read_exr(&mut BufReader::new(File::open(path)?))?
+        } else {
+            let image = image::open(path)
+                .map_err(|e| match e {
+                    ImageError::IoError(e) => e,
+                    e => io::Error::new(io::ErrorKind::InvalidData, e.to_string()),
+                })?
+                .to_rgb();
+            let pixels = image
+                .pixels()
+                .map(|p| {
+                    Vector3::new(
+                        srgb_to_linear(f32::from(p[0]) / 255.0),
+                        srgb_to_linear(f32::from(p[1]) / 255.0),
+                        srgb_to_linear(f32::from(p[2]) / 255.0),
+                    )
+                })
+                .collect();
+            (image.width() as usize, image.height() as usize, pixels)
+        };
+        if pixels.is_empty() {
+            return Err(io::Error::new(io::ErrorKind::InvalidData, "empty image"));
+        }
+
+        Ok(ImageTexture {
+            width,
+            height,
+            pixels,
+            wrap,
+            scale,
+        })
+    }
+
+    fn texel(&self, x: i64, y: i64) -> Vector3<f32> {
+        let x = self.wrap.wrap(x, self.width);
+        let y = self.wrap.wrap(y, self.height);
+        self.pixels[y * self.width + x]
+    }
+}
+
+impl Texture for ImageTexture {
+    // Bilinear interpolation between the centers of the four closest texels, with v = 0 at the
+    // bottom of the image.
+    fn value(&self, hit: &HitRecord) -> Vector3<f32> {
+        let x = hit.uv[0] * self.scale[0] * self.width as f32 - 0.5;
+        let y = (1.0 - hit.uv[1] * self.scale[1]) * self.height as f32 - 0.5;
+        let (x0, y0) = (x.floor(), y.floor());
+        let (tx, ty) = (x - x0, y - y0);
+        let (x0, y0) = (x0 as i64, y0 as i64);
+
+        let top = (1.0 - tx) * self.texel(x0, y0) + tx * self.texel(x0 + 1, y0);
+        let bottom = (1.0 - tx) * self.texel(x0, y0 + 1) + tx * self.texel(x0 + 1, y0 + 1);
+        (1.0 - ty) * top + ty * bottom
+    }
+}
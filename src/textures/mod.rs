@@ -0,0 +1,114 @@
+use std::path::Path;
+
+use nalgebra::{Vector2, Vector3};
+use serde::{Deserialize, Serialize};
+
+use crate::error::{Error, Result};
+use crate::object::HitRecord;
+
+pub trait Texture: Send + Sync {
+    fn value(&self, hit: &HitRecord) -> Vector3<f32>;
+}
+
+mod checkerboard;
+mod constant;
+mod exr;
+mod image_texture;
+mod noise;
+
+pub use checkerboard::CheckerboardTexture;
+pub use constant::ConstantTexture;
+pub use image_texture::{ImageTexture, WrapMode};
+pub use noise::NoiseTexture;
+
+fn default_uv_scale() -> Vector2<f32> {
+    Vector2::new(1.0, 1.0)
+}
+
+fn default_checkerboard_scale() -> f32 {
+    8.0
+}
+
+fn default_noise_scale() -> f32 {
+    1.0
+}
+
+fn default_noise_octaves() -> u32 {
+    4
+}
+
+/// Color of a material, either a constant RGB triplet or a texture.
+#[derive(Clone, Serialize, Deserialize)]
+#[serde(untagged)]
+pub enum ColorData {
+    Constant(Vector3<f32>),
+    Texture(TextureData),
+}
+
+#[derive(Clone, Serialize, Deserialize)]
+pub enum TextureData {
+    // PNG and JPEG images are sRGB encoded, OpenEXR images are linear.
+    Image {
+        path: String,
+        #[serde(default)]
+        wrap: WrapMode,
+        #[serde(default = "default_uv_scale")]
+        scale: Vector2<f32>,
+    },
+    Checkerboard {
+        even: Box<ColorData>,
+        odd: Box<ColorData>,
+        // Number of squares along each texture coordinate.
+        #[serde(default = "default_checkerboard_scale")]
+        scale: f32,
+    },
+    // Fractal Perlin noise in world space, blending between two colors.
+    Noise {
+        low: Box<ColorData>,
+        high: Box<ColorData>,
+        #[serde(default = "default_noise_scale")]
+        scale: f32,
+        #[serde(default = "default_noise_octaves")]
+        octaves: u32,
+        #[serde(default)]
+        seed: u64,
+    },
+}
+
+impl ColorData {
+    pub fn to_texture(self) -> Result<Box<dyn Texture>> {
+        match self {
+            ColorData::Constant(color) => Ok(Box::new(ConstantTexture::new(color))),
+            ColorData::Texture(texture) => texture.to_texture(),
+        }
+    }
+}
+
+impl TextureData {
+    pub fn to_texture(self) -> Result<Box<dyn Texture>> {
+        Ok(match self {
+            TextureData::Image { path, wrap, scale } => Box::new(
+                ImageTexture::load(&path, wrap, scale)
+                    .map_err(|e| Error::from_io(Path::new(&path), e))?,
+            ),
+            TextureData::Checkerboard { even, odd, scale } => Box::new(CheckerboardTexture::new(
+                even.to_texture()?,
+                odd.to_texture()?,
+                scale,
+            )),
+            TextureData::Noise {
+                low,
+                high,
+                scale,
+                octaves,
+                seed,
+            } => Box::new(NoiseTexture::new(
+                low.to_texture()?,
+                high.to_texture()?,
+                scale,
+                octaves,
+                seed,
+            )),
+        })
+    }
+}
@@ -0,0 +1,109 @@
+use nalgebra::{Point3, Vector3};
+use rand::seq::SliceRandom;
+use rand_pcg::Pcg32;
+
+use crate::object::HitRecord;
+use crate::textures::Texture;
+
+/// Fractal sum of Perlin's improved gradient noise, see Perlin, "Improving Noise", 2002.
+pub struct NoiseTexture {
+    low: Box<dyn Texture>,
+    high: Box<dyn Texture>,
+    scale: f32,
+    octaves: u32,
+    // Permutation of 0..256, repeated once to avoid wrapping the indices.
+    permutation: Vec<usize>,
+}
+
+impl NoiseTexture {
+    pub fn new(
+        low: Box<dyn Texture>,
+        high: Box<dyn Texture>,
+        scale: f32,
+        octaves: u32,
+        seed: u64,
+    ) -> Self {
+        let mut permutation = (0..256).collect::<Vec<usize>>();
+        permutation.shuffle(&mut Pcg32::new(seed, 0));
+        NoiseTexture {
+            low,
+            high,
+            scale,
+            octaves: octaves.max(1),
+            permutation: [&permutation[..], &permutation[..]].concat(),
+        }
+    }
+
+    // Gradient noise in [-1, 1].
+    fn noise(&self, p: &Point3<f32>) -> f32 {
+        let cell = p.coords.map(|x| x.floor());
+        let local = p.coords - cell;
+        let x = (cell[0] as i64).rem_euclid(256) as usize;
+        let y = (cell[1] as i64).rem_euclid(256) as usize;
+        let z = (cell[2] as i64).rem_euclid(256) as usize;
+        let fade = local.map(|t| t * t * t * (t * (t * 6.0 - 15.0) + 10.0));
+
+        let hash = |i: usize, j: usize, k: usize| {
+            self.permutation[self.permutation[self.permutation[x + i] + y + j] + z + k]
+        };
+        let corner = |i: usize, j: usize, k: usize| {
+            gradient(
+                hash(i, j, k),
+                &(local - Vector3::new(i as f32, j as f32, k as f32)),
+            )
+        };
+        let lerp = |t: f32, a: f32, b: f32| a + t * (b - a);
+
+        lerp(
+            fade[2],
+            lerp(
+                fade[1],
+                lerp(fade[0], corner(0, 0, 0), corner(1, 0, 0)),
+                lerp(fade[0], corner(0, 1, 0), corner(1, 1, 0)),
+            ),
+            lerp(
+                fade[1],
+                lerp(fade[0], corner(0, 0, 1), corner(1, 0, 1)),
+                lerp(fade[0], corner(0, 1, 1), corner(1, 1, 1)),
+            ),
+        )
+    }
+
+    // Sum of octaves of noise, remapped to [0, 1].
+    fn fractal_noise(&self, p: &Point3<f32>) -> f32 {
+        let mut value = 0.0;
+        let mut amplitude = 1.0;
+        let mut total_amplitude = 0.0;
+        let mut frequency = self.scale;
+        for _ in 0..self.octaves {
+            value += amplitude * self.noise(&(p * frequency));
+            total_amplitude += amplitude;
+            amplitude *= 0.5;
+            frequency *= 2.0;
+        }
+        (0.5 + 0.5 * value / total_amplitude).clamp(0.0, 1.0)
+    }
+}
+
+// Dot product with one of the 12 gradient directions towards the edges of a cube.
+fn gradient(hash: usize, offset: &Vector3<f32>) -> f32 {
+    let h = hash & 15;
+    let u = if h < 8 { offset[0] } else { offset[1] };
+    let v = if h < 4 {
+        offset[1]
+    } else if h == 12 || h == 14 {
+        offset[0]
+    } else {
+        offset[2]
+    };
+    let u = if h & 1 == 0 { u } else { -u };
+    let v = if h & 2 == 0 { v } else { -v };
+    u + v
+}
+
+impl Texture for NoiseTexture {
+    fn value(&self, hit: &HitRecord) -> Vector3<f32> {
+        let t = self.fractal_noise(&hit.point);
+        (1.0 - t) * self.low.value(hit) + t * self.high.value(hit)
+    }
+}